@@ -0,0 +1,138 @@
+//! Compares BVH builders and traversal: the recursive `BvhNode` built with
+//! the median and SAH splits against the flattened `LinearBvh`.
+//!
+//! Run with `cargo bench --bench bvh`.
+
+use rayt::bvh::{BvhNode, BvhOptions, LinearBvh, SplitMethod};
+use rayt::circle::Sphere;
+use rayt::hittable::Hittable;
+use rayt::hittable_list::HittableList;
+use rayt::material::{Lambertian, Material};
+use rayt::ray::Ray;
+use rayt::sampler::Independent;
+use rayt::triangle::TriangleMesh;
+use rayt::util;
+use rayt::vec::{self, Color, Point};
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const RAYS: usize = 500_000;
+
+/// Small spheres scattered over a huge ground sphere, like the cover scene.
+fn spheres(material: &Arc<dyn Material>) -> Vec<Arc<dyn Hittable>> {
+    let mut objects: Vec<Arc<dyn Hittable>> = vec![Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material.clone(),
+    ))];
+    for _ in 0..2000 {
+        let center = Point::new(
+            util::random_f64_range(-20.0..20.0),
+            0.2,
+            util::random_f64_range(-20.0..20.0),
+        );
+        objects.push(Arc::new(Sphere::new(center, 0.2, material.clone())));
+    }
+    objects
+}
+
+/// A bumpy 256x256 grid of quads, split into triangles.
+fn terrain(material: &Arc<dyn Material>) -> Vec<Arc<dyn Hittable>> {
+    let n = 256;
+    let mut positions = Vec::new();
+    for z in 0..=n {
+        for x in 0..=n {
+            let (fx, fz) = (
+                x as f64 / n as f64 * 40.0 - 20.0,
+                z as f64 / n as f64 * 40.0 - 20.0,
+            );
+            positions.push(Point::new(fx, (fx * 0.5).sin() * (fz * 0.3).cos(), fz));
+        }
+    }
+    let mut indices = Vec::new();
+    for z in 0..n {
+        for x in 0..n {
+            let i = z * (n + 1) + x;
+            indices.push([i, i + 1, i + n + 1]);
+            indices.push([i + 1, i + n + 2, i + n + 1]);
+        }
+    }
+    Arc::new(TriangleMesh::new(
+        positions,
+        None,
+        None,
+        indices,
+        material.clone(),
+    ))
+    .triangles()
+}
+
+/// Rays from above the scene towards random points on the ground.
+fn rays() -> Vec<Ray> {
+    (0..RAYS)
+        .map(|_| {
+            let origin = Point::new(
+                util::random_f64_range(-25.0..25.0),
+                util::random_f64_range(2.0..10.0),
+                util::random_f64_range(-25.0..25.0),
+            );
+            let target = Point::new(
+                util::random_f64_range(-20.0..20.0),
+                0.0,
+                util::random_f64_range(-20.0..20.0),
+            );
+            Ray::new(origin, target - origin + vec::random() * 0.1, 0.0)
+        })
+        .collect()
+}
+
+fn trace(name: &str, build: Duration, bvh: &dyn Hittable, rays: &[Ray]) {
+    let start = Instant::now();
+    let mut hits = 0;
+    for r in rays {
+        if black_box(bvh.hit(r, 0.001..f64::INFINITY, &mut Independent)).is_some() {
+            hits += 1;
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "  {:<16} build {:>8.2} ms   trace {:>8.2} ms   {:>6.2} Mrays/s   {} hits",
+        name,
+        build.as_secs_f64() * 1000.0,
+        elapsed.as_secs_f64() * 1000.0,
+        rays.len() as f64 / elapsed.as_secs_f64() / 1e6,
+        hits
+    );
+}
+
+fn bench(scene: &str, objects: Vec<Arc<dyn Hittable>>, rays: &[Ray]) {
+    println!("{} ({} objects, {} rays)", scene, objects.len(), rays.len());
+
+    let list = HittableList { objects };
+    for (name, split_method) in [
+        ("BvhNode median", SplitMethod::Median),
+        ("BvhNode SAH", SplitMethod::Sah),
+    ] {
+        let options = BvhOptions {
+            split_method,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let bvh = BvhNode::with_options(&list, 0.0..1.0, &options).unwrap();
+        trace(name, start.elapsed(), &bvh, rays);
+    }
+
+    let start = Instant::now();
+    let bvh = LinearBvh::new(list.objects, 0.0..1.0).unwrap();
+    trace("LinearBvh SAH", start.elapsed(), &bvh, rays);
+}
+
+fn main() {
+    util::seed(1);
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let rays = rays();
+
+    bench("spheres", spheres(&material), &rays);
+    bench("terrain", terrain(&material), &rays);
+}
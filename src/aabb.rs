@@ -2,7 +2,7 @@ use crate::ray::Ray;
 use crate::vec::{Point, Vec};
 use std::ops::Range;
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug)]
 pub struct Aabb {
     maximum: Point,
     minimum: Point,
@@ -21,7 +21,19 @@ impl Aabb {
         &self.maximum
     }
 
-    pub fn hit(&self, r: &Ray, time_range: Range<f64>) -> bool {
+    pub fn centroid(&self) -> Point {
+        (self.minimum + self.maximum) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Slab test: each axis narrows the span of `t` the ray can spend
+    /// inside the box, which is empty if the ray misses.
+    pub fn hit(&self, r: &Ray, range: Range<f64>) -> bool {
+        let (mut t_min, mut t_max) = (range.start, range.end);
         for i in 0..3 {
             let inv_d = 1.0 / r.direction()[i];
 
@@ -30,22 +42,17 @@ impl Aabb {
 
             let (t0, t1) = if inv_d < 0.0 { (t1, t0) } else { (t0, t1) };
 
-            let t_min = if t0 > time_range.start {
-                t0
-            } else {
-                time_range.start
-            };
-            let t_max = if t1 < time_range.end {
-                t1
-            } else {
-                time_range.end
-            };
+            // The span carries over from axis to axis. Checking each axis
+            // against the whole range on its own would count rays that
+            // cross the three slabs at different times as hits.
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
 
             if t_max <= t_min {
                 return false;
             }
         }
-        return true;
+        true
     }
 
     pub fn surrounding_box(box0: Aabb, box1: Aabb) -> Aabb {
@@ -63,3 +70,22 @@ impl Aabb {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slab_test_needs_one_span_across_axes() {
+        let bbox = Aabb::new(Vec::new(0.0, 0.0, 0.0), Vec::new(1.0, 1.0, 1.0));
+        // Inside the x slab for t in 1..2 and the y slab for t in 3..4, so
+        // never inside the box.
+        let r = Ray::new(Vec::new(-1.0, -3.0, 0.5), Vec::new(1.0, 1.0, 0.0), 0.0);
+        assert!(!bbox.hit(&r, 0.0..f64::INFINITY));
+
+        let r = Ray::new(Vec::new(-1.0, -1.0, 0.5), Vec::new(1.0, 1.0, 0.0), 0.0);
+        assert!(bbox.hit(&r, 0.0..f64::INFINITY));
+        assert!(!bbox.hit(&r, 0.0..0.5));
+        assert!(!bbox.hit(&r, 2.5..f64::INFINITY));
+    }
+}
@@ -0,0 +1,96 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::rect::{XyRect, XzRect, YzRect};
+use crate::sampler::Sampler;
+use crate::vec::{Point, Vec};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// An axis-aligned box made of six rectangles.
+pub struct BoxShape {
+    minimum: Point,
+    maximum: Point,
+    sides: HittableList,
+}
+
+impl BoxShape {
+    pub fn new(p0: Point, p1: Point, material: Arc<dyn Material>) -> Self {
+        let minimum = p0.inf(&p1);
+        let maximum = p0.sup(&p1);
+        let (x, y, z) = (
+            minimum.x..maximum.x,
+            minimum.y..maximum.y,
+            minimum.z..maximum.z,
+        );
+
+        let mut sides = HittableList::new();
+        sides.add(Arc::new(XyRect::new(
+            x.clone(),
+            y.clone(),
+            maximum.z,
+            material.clone(),
+        )));
+        sides.add(Arc::new(XyRect::new(
+            x.clone(),
+            y.clone(),
+            minimum.z,
+            material.clone(),
+        )));
+        sides.add(Arc::new(XzRect::new(
+            x.clone(),
+            z.clone(),
+            maximum.y,
+            material.clone(),
+        )));
+        sides.add(Arc::new(XzRect::new(
+            x,
+            z.clone(),
+            minimum.y,
+            material.clone(),
+        )));
+        sides.add(Arc::new(YzRect::new(
+            y.clone(),
+            z.clone(),
+            maximum.x,
+            material.clone(),
+        )));
+        sides.add(Arc::new(YzRect::new(y, z, minimum.x, material)));
+
+        Self {
+            minimum,
+            maximum,
+            sides,
+        }
+    }
+}
+
+impl Hittable for BoxShape {
+    fn hit(&self, r: &Ray, range: Range<f64>, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        self.sides.hit(r, range, sampler)
+    }
+
+    fn hit_counting(
+        &self,
+        r: &Ray,
+        range: Range<f64>,
+        tests: &mut usize,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord> {
+        self.sides.hit_counting(r, range, tests, sampler)
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> Option<Aabb> {
+        Some(Aabb::new(self.minimum, self.maximum))
+    }
+
+    fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<Vec> {
+        self.sides.sample_towards(origin, sampler)
+    }
+
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        self.sides.pdf_towards(r, sampler)
+    }
+}
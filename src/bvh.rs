@@ -1,87 +1,694 @@
 use crate::aabb::Aabb;
+use crate::hittable as h;
 use crate::hittable_list::HittableList;
-use crate::{hittable as h, util};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec::{self, Point};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::fmt;
 use std::ops::Range;
-use std::rc::Rc;
+use std::sync::Arc;
+
+/// How [`BvhNode`] divides the objects of a node between its children.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitMethod {
+    /// Binned surface area heuristic: tries a few split planes along every
+    /// axis and keeps the one with the lowest expected traversal cost.
+    Sah,
+    /// Sorts along a random axis and splits at the median, as the first
+    /// builder did. Kept around for comparison.
+    Median,
+}
+
+#[derive(Clone, Debug)]
+pub struct BvhOptions {
+    pub split_method: SplitMethod,
+    /// Nodes with more objects than this are always split. Clamped to
+    /// `1..=u16::MAX`.
+    pub max_leaf_size: usize,
+    /// Number of candidate split planes per axis for [`SplitMethod::Sah`].
+    pub sah_bins: usize,
+    /// Seeds the axis choices of [`SplitMethod::Median`].
+    pub seed: u64,
+}
+
+impl Default for BvhOptions {
+    fn default() -> Self {
+        Self {
+            split_method: SplitMethod::Sah,
+            max_leaf_size: 4,
+            sah_bins: 16,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BvhError {
+    Empty,
+    /// The object at this index in the input has no bounding box.
+    Unbounded(usize),
+}
+
+impl fmt::Display for BvhError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BvhError::Empty => write!(f, "cannot build a BVH over no objects"),
+            BvhError::Unbounded(index) => write!(f, "object {} has no bounding box", index),
+        }
+    }
+}
+
+impl std::error::Error for BvhError {}
+
+/// Cost of testing a ray against a node's box, relative to testing it
+/// against one object.
+const TRAVERSAL_COST: f64 = 0.5;
+
+/// Past this depth the SAH builder gives up on cost and halves nodes, which
+/// bounds the depth of the tree by `MAX_SAH_DEPTH + 32`.
+const MAX_SAH_DEPTH: usize = 32;
 
 pub struct BvhNode {
-    left: Rc<dyn h::Hittable>,
-    right: Rc<dyn h::Hittable>,
     bbox: Aabb,
+    contents: Contents,
+}
+
+enum Contents {
+    Leaf(Vec<Arc<dyn h::Hittable>>),
+    /// The children and the axis they were split along.
+    Split(Box<BvhNode>, Box<BvhNode>, usize),
+}
+
+/// An object waiting to be placed in the tree, with its box computed once.
+struct Primitive {
+    object: Arc<dyn h::Hittable>,
+    bbox: Aabb,
+    centroid: Point,
 }
 
 impl BvhNode {
-    pub fn new(list: &HittableList, time_range: Range<f64>) -> Self {
-        Self::make(&mut list.objects.clone()[..], time_range)
+    pub fn new(list: &HittableList, time_range: Range<f64>) -> Result<Self, BvhError> {
+        Self::with_options(list, time_range, &BvhOptions::default())
     }
 
-    fn box_compare(
-        a: &Rc<dyn h::Hittable>,
-        b: &Rc<dyn h::Hittable>,
-        axis: usize,
-    ) -> std::cmp::Ordering {
-        let box_a = a.bounding_box(0.0..0.0);
-        let box_b = b.bounding_box(0.0..0.0);
-        if box_a.is_none() || box_b.is_none() {
-            eprintln!("No bouding box in bvh_node constructor.\n");
-        }
+    /// Boxes are taken over `time_range`, which should be the camera's
+    /// shutter interval so that moving objects stay inside them.
+    pub fn with_options(
+        list: &HittableList,
+        time_range: Range<f64>,
+        options: &BvhOptions,
+    ) -> Result<Self, BvhError> {
+        Self::build(list.objects.iter().cloned(), time_range, options)
+    }
 
-        if box_a.unwrap().min()[axis] < box_b.unwrap().min()[axis] {
-            std::cmp::Ordering::Less
-        } else {
-            std::cmp::Ordering::Greater
+    fn build(
+        objects: impl Iterator<Item = Arc<dyn h::Hittable>>,
+        time_range: Range<f64>,
+        options: &BvhOptions,
+    ) -> Result<Self, BvhError> {
+        let mut primitives = objects
+            .enumerate()
+            .map(|(index, object)| {
+                let bbox = object
+                    .bounding_box(time_range.clone())
+                    .ok_or(BvhError::Unbounded(index))?;
+                Ok(Primitive {
+                    object,
+                    bbox,
+                    centroid: bbox.centroid(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if primitives.is_empty() {
+            return Err(BvhError::Empty);
         }
+
+        let options = BvhOptions {
+            max_leaf_size: options.max_leaf_size.clamp(1, u16::MAX as usize),
+            ..options.clone()
+        };
+        let mut rng = SmallRng::seed_from_u64(options.seed);
+        Ok(Self::make(&mut primitives, &options, &mut rng, 0))
     }
 
-    fn make(objects: &mut [Rc<dyn h::Hittable>], time_range: Range<f64>) -> Self {
-        let axis = util::random_i32_range(0..=2) as usize;
+    fn make(
+        primitives: &mut [Primitive],
+        options: &BvhOptions,
+        rng: &mut SmallRng,
+        depth: usize,
+    ) -> Self {
+        let bbox = Self::enclose(primitives.iter().map(|p| p.bbox));
+        let split = match options.split_method {
+            SplitMethod::Sah if depth < MAX_SAH_DEPTH => {
+                Self::split_sah(primitives, &bbox, options)
+            }
+            SplitMethod::Sah => Self::split_halves(primitives, options),
+            SplitMethod::Median => Self::split_median(primitives, options, rng),
+        };
 
-        let (left, right) = if objects.len() == 1 {
-            (objects[0].clone(), objects[0].clone())
-        } else if objects.len() == 2 {
-            if Self::box_compare(&objects[0], &objects[1], axis) == std::cmp::Ordering::Less {
-                (objects[0].clone(), objects[1].clone())
-            } else {
-                (objects[1].clone(), objects[0].clone())
+        let contents = match split {
+            Some((mid, axis)) => {
+                let (left, right) = primitives.split_at_mut(mid);
+                Contents::Split(
+                    Box::new(Self::make(left, options, rng, depth + 1)),
+                    Box::new(Self::make(right, options, rng, depth + 1)),
+                    axis,
+                )
             }
-        } else {
-            objects.sort_by(|a, b| Self::box_compare(a, b, axis));
+            None => Contents::Leaf(primitives.iter().map(|p| p.object.clone()).collect()),
+        };
+        Self { bbox, contents }
+    }
+
+    fn enclose(boxes: impl Iterator<Item = Aabb>) -> Aabb {
+        boxes.reduce(Aabb::surrounding_box).unwrap()
+    }
+
+    /// Reorders `primitives` so that the left child gets the ones before the
+    /// returned index, and returns that index with the axis the children are
+    /// split along. Returns `None` to make a leaf.
+    fn split_median(
+        primitives: &mut [Primitive],
+        options: &BvhOptions,
+        rng: &mut SmallRng,
+    ) -> Option<(usize, usize)> {
+        if primitives.len() <= options.max_leaf_size {
+            return None;
+        }
 
-            let mid = objects.len() / 2;
-            let left: Rc<dyn h::Hittable> =
-                Rc::new(Self::make(&mut objects[..mid], time_range.clone()));
-            let right: Rc<dyn h::Hittable> =
-                Rc::new(Self::make(&mut objects[mid..], time_range.clone()));
-            (left, right)
+        let axis = rng.gen_range(0..3);
+        primitives.sort_by(|a, b| a.bbox.min()[axis].total_cmp(&b.bbox.min()[axis]));
+        Some((primitives.len() / 2, axis))
+    }
+
+    /// Halves along the axis where the centroids are most spread out.
+    fn split_halves(primitives: &mut [Primitive], options: &BvhOptions) -> Option<(usize, usize)> {
+        if primitives.len() <= options.max_leaf_size {
+            return None;
+        }
+
+        let centroid_bounds =
+            Self::enclose(primitives.iter().map(|p| Aabb::new(p.centroid, p.centroid)));
+        let axis = (centroid_bounds.max() - centroid_bounds.min()).imax();
+        let mid = primitives.len() / 2;
+        primitives
+            .select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        Some((mid, axis))
+    }
+
+    fn split_sah(
+        primitives: &mut [Primitive],
+        bbox: &Aabb,
+        options: &BvhOptions,
+    ) -> Option<(usize, usize)> {
+        let n = primitives.len();
+        if n == 1 {
+            return None;
+        }
+
+        let centroid_bounds =
+            Self::enclose(primitives.iter().map(|p| Aabb::new(p.centroid, p.centroid)));
+        let bins = options.sah_bins.max(2);
+        let bin_of = |p: &Primitive, axis: usize| {
+            let min = centroid_bounds.min()[axis];
+            let extent = centroid_bounds.max()[axis] - min;
+            (((p.centroid[axis] - min) / extent * bins as f64) as usize).min(bins - 1)
         };
 
-        Self {
-            bbox: Aabb::surrounding_box(
-                left.bounding_box(time_range.clone()).unwrap(),
-                right.bounding_box(time_range.clone()).unwrap(),
-            ),
-            left,
-            right,
+        // (cost, axis, first bin of the right child)
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            if centroid_bounds.max()[axis] <= centroid_bounds.min()[axis] {
+                continue;
+            }
+
+            let mut counts = vec![0usize; bins];
+            let mut boxes: Vec<Option<Aabb>> = vec![None; bins];
+            for p in primitives.iter() {
+                let b = bin_of(p, axis);
+                counts[b] += 1;
+                boxes[b] = Some(match boxes[b] {
+                    Some(bin_box) => Aabb::surrounding_box(bin_box, p.bbox),
+                    None => p.bbox,
+                });
+            }
+
+            // Sweep from the right to get the cost of everything right of
+            // each plane, then from the left to finish each candidate.
+            let mut right_costs = vec![0.0; bins];
+            let (mut count, mut acc) = (0, None);
+            for b in (1..bins).rev() {
+                count += counts[b];
+                acc = Self::grow(acc, boxes[b]);
+                right_costs[b] = acc.map_or(0.0, |a| a.surface_area() * count as f64);
+            }
+
+            let (mut count, mut acc) = (0, None);
+            for b in 1..bins {
+                count += counts[b - 1];
+                acc = Self::grow(acc, boxes[b - 1]);
+                if count == 0 || count == n {
+                    continue;
+                }
+                let left_cost = acc.map_or(0.0, |a| a.surface_area() * count as f64);
+                let cost = TRAVERSAL_COST + (left_cost + right_costs[b]) / bbox.surface_area();
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, b));
+                }
+            }
+        }
+
+        match best {
+            Some((cost, axis, first_right)) => {
+                if n <= options.max_leaf_size && cost >= n as f64 {
+                    return None;
+                }
+                let mut mid = 0;
+                for i in 0..n {
+                    if bin_of(&primitives[i], axis) < first_right {
+                        primitives.swap(i, mid);
+                        mid += 1;
+                    }
+                }
+                Some((mid, axis))
+            }
+            // All the centroids coincide, so no plane separates anything.
+            None if n <= options.max_leaf_size => None,
+            None => Some((n / 2, 0)),
+        }
+    }
+
+    fn grow(acc: Option<Aabb>, bbox: Option<Aabb>) -> Option<Aabb> {
+        match (acc, bbox) {
+            (Some(a), Some(b)) => Some(Aabb::surrounding_box(a, b)),
+            (a, b) => a.or(b),
         }
     }
 }
 
 impl h::Hittable for BvhNode {
-    fn hit(&self, r: &crate::ray::Ray, range: Range<f64>) -> Option<h::HitRecord> {
+    fn hit(&self, r: &Ray, range: Range<f64>, sampler: &mut dyn Sampler) -> Option<h::HitRecord> {
         if !self.bbox.hit(r, range.clone()) {
-            None
-        } else {
-            if let Some(hit_left) = self.left.hit(r, range.clone()) {
-                self.right
-                    .hit(r, range.start..hit_left.t)
-                    .or(Some(hit_left))
-            } else {
-                self.right.hit(r, range)
+            return None;
+        }
+
+        match &self.contents {
+            Contents::Leaf(objects) => {
+                let mut closest = None;
+                let mut end = range.end;
+                for object in objects {
+                    if let Some(rec) = object.hit(r, range.start..end, sampler) {
+                        end = rec.t;
+                        closest = Some(rec);
+                    }
+                }
+                closest
+            }
+            Contents::Split(left, right, _) => {
+                if let Some(hit_left) = left.hit(r, range.clone(), sampler) {
+                    right
+                        .hit(r, range.start..hit_left.t, sampler)
+                        .or(Some(hit_left))
+                } else {
+                    right.hit(r, range, sampler)
+                }
+            }
+        }
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+/// The same tree as [`BvhNode`] packed into one array, depth first, so that
+/// traversal walks memory instead of chasing pointers. Each node's first
+/// child directly follows it; leaves index a run of `objects`.
+pub struct LinearBvh {
+    nodes: Vec<LinearNode>,
+    objects: Vec<Arc<dyn h::Hittable>>,
+}
+
+struct LinearNode {
+    bbox: Aabb,
+    /// Leaves: index of the first object. Interior nodes: index of the
+    /// second child.
+    offset: u32,
+    /// Number of objects, zero for interior nodes.
+    count: u16,
+    axis: u8,
+}
+
+impl LinearBvh {
+    pub fn new(
+        objects: Vec<Arc<dyn h::Hittable>>,
+        time_range: Range<f64>,
+    ) -> Result<Self, BvhError> {
+        Self::with_options(objects, time_range, &BvhOptions::default())
+    }
+
+    pub fn with_options(
+        objects: Vec<Arc<dyn h::Hittable>>,
+        time_range: Range<f64>,
+        options: &BvhOptions,
+    ) -> Result<Self, BvhError> {
+        let count = objects.len();
+        let tree = BvhNode::build(objects.into_iter(), time_range, options)?;
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            objects: Vec::with_capacity(count),
+        };
+        bvh.flatten(tree);
+        Ok(bvh)
+    }
+
+    fn flatten(&mut self, node: BvhNode) {
+        let index = self.nodes.len();
+        self.nodes.push(LinearNode {
+            bbox: node.bbox,
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+
+        match node.contents {
+            Contents::Leaf(objects) => {
+                self.nodes[index].offset = self.objects.len() as u32;
+                // `build` clamps the leaf size to fit.
+                self.nodes[index].count = objects.len() as u16;
+                self.objects.extend(objects);
+            }
+            Contents::Split(left, right, axis) => {
+                self.flatten(*left);
+                self.nodes[index].offset = self.nodes.len() as u32;
+                self.nodes[index].axis = axis as u8;
+                self.flatten(*right);
             }
         }
     }
 
+    /// The closest hit along `r`, also counting node and object tests into
+    /// `tests` when `COUNT` is set. Plain hits compile without the counting.
+    fn traverse<const COUNT: bool>(
+        &self,
+        r: &Ray,
+        range: Range<f64>,
+        tests: &mut usize,
+        sampler: &mut dyn Sampler,
+    ) -> Option<h::HitRecord> {
+        let negative = [
+            r.direction().x < 0.0,
+            r.direction().y < 0.0,
+            r.direction().z < 0.0,
+        ];
+        let mut closest = None;
+        let mut end = range.end;
+
+        // Far children waiting to be visited; see `MAX_SAH_DEPTH`.
+        let mut stack = [0u32; 64];
+        let mut stack_len = 0;
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            if COUNT {
+                *tests += 1;
+            }
+            if node.bbox.hit(r, range.start..end) {
+                if node.count > 0 {
+                    let first = node.offset as usize;
+                    for object in &self.objects[first..first + node.count as usize] {
+                        let rec = if COUNT {
+                            object.hit_counting(r, range.start..end, tests, sampler)
+                        } else {
+                            object.hit(r, range.start..end, sampler)
+                        };
+                        if let Some(rec) = rec {
+                            end = rec.t;
+                            closest = Some(rec);
+                        }
+                    }
+                } else {
+                    // Visit the child on the side the ray comes from first,
+                    // so that its hits can cull the other one.
+                    let (near, far) = if negative[node.axis as usize] {
+                        (node.offset, index as u32 + 1)
+                    } else {
+                        (index as u32 + 1, node.offset)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    index = near as usize;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                return closest;
+            }
+            stack_len -= 1;
+            index = stack[stack_len] as usize;
+        }
+    }
+}
+
+impl h::Hittable for LinearBvh {
+    fn hit(&self, r: &Ray, range: Range<f64>, sampler: &mut dyn Sampler) -> Option<h::HitRecord> {
+        self.traverse::<false>(r, range, &mut 0, sampler)
+    }
+
+    fn hit_counting(
+        &self,
+        r: &Ray,
+        range: Range<f64>,
+        tests: &mut usize,
+        sampler: &mut dyn Sampler,
+    ) -> Option<h::HitRecord> {
+        self.traverse::<true>(r, range, tests, sampler)
+    }
+
     fn bounding_box(&self, _time_range: Range<f64>) -> Option<Aabb> {
-        Some(self.bbox.clone())
+        Some(self.nodes[0].bbox)
+    }
+
+    /// Samples one of the objects, chosen uniformly.
+    fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<vec::Vec> {
+        let index = (sampler.get_1d() * self.objects.len() as f64) as usize;
+        self.objects[index.min(self.objects.len() - 1)].sample_towards(origin, sampler)
+    }
+
+    /// Asks every object, not just those along `r`: any of them may have
+    /// been sampled, and the tree only finds the closest hit.
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_towards(r, sampler))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+}
+
+/// What a scene is traced against: a [`LinearBvh`] over every object with a
+/// bounding box, and a plain list, tested one by one, of the objects
+/// without one.
+pub struct Accelerator {
+    bounded: Option<LinearBvh>,
+    unbounded: HittableList,
+}
+
+impl Accelerator {
+    pub fn new(
+        objects: Vec<Arc<dyn h::Hittable>>,
+        time_range: Range<f64>,
+        options: &BvhOptions,
+    ) -> Self {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|object| object.bounding_box(time_range.clone()).is_some());
+
+        Self {
+            // Can't fail: the objects are bounded and there is at least one.
+            bounded: (!bounded.is_empty())
+                .then(|| LinearBvh::with_options(bounded, time_range, options).unwrap()),
+            unbounded: HittableList { objects: unbounded },
+        }
+    }
+}
+
+impl h::Hittable for Accelerator {
+    fn hit(&self, r: &Ray, range: Range<f64>, sampler: &mut dyn Sampler) -> Option<h::HitRecord> {
+        let closest = self
+            .bounded
+            .as_ref()
+            .and_then(|bvh| bvh.hit(r, range.clone(), sampler));
+        let end = closest.as_ref().map_or(range.end, |rec| rec.t);
+        self.unbounded.hit(r, range.start..end, sampler).or(closest)
+    }
+
+    fn hit_counting(
+        &self,
+        r: &Ray,
+        range: Range<f64>,
+        tests: &mut usize,
+        sampler: &mut dyn Sampler,
+    ) -> Option<h::HitRecord> {
+        let closest = self
+            .bounded
+            .as_ref()
+            .and_then(|bvh| bvh.hit_counting(r, range.clone(), tests, sampler));
+        let end = closest.as_ref().map_or(range.end, |rec| rec.t);
+        self.unbounded
+            .hit_counting(r, range.start..end, tests, sampler)
+            .or(closest)
+    }
+
+    fn bounding_box(&self, time_range: Range<f64>) -> Option<Aabb> {
+        if !self.unbounded.objects.is_empty() {
+            return None;
+        }
+        self.bounded
+            .as_ref()
+            .and_then(|bvh| bvh.bounding_box(time_range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circle::Sphere;
+    use crate::hittable::Hittable;
+    use crate::material::Lambertian;
+    use crate::sampler::Independent;
+    use crate::triangle::Triangle;
+    use crate::vec::Color;
+
+    /// Spheres and triangles of all sizes scattered over a 20 unit cube.
+    fn objects(rng: &mut SmallRng) -> Vec<Arc<dyn h::Hittable>> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let point = |rng: &mut SmallRng| {
+            Point::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+            )
+        };
+        let mut objects: Vec<Arc<dyn h::Hittable>> = Vec::new();
+        for _ in 0..150 {
+            let center = point(rng);
+            let radius = rng.gen_range(0.05..1.5);
+            objects.push(Arc::new(Sphere::new(center, radius, material.clone())));
+
+            let a = point(rng);
+            let near = |rng: &mut SmallRng| a + (point(rng) - a) * rng.gen_range(0.01..0.3);
+            let (b, c) = (near(rng), near(rng));
+            objects.push(Arc::new(Triangle::new(a, b, c, material.clone())));
+        }
+        objects
+    }
+
+    fn random_ray(rng: &mut SmallRng) -> Ray {
+        let mut coordinate = |range: Range<f64>| {
+            vec::Vec::new(
+                rng.gen_range(range.clone()),
+                rng.gen_range(range.clone()),
+                rng.gen_range(range),
+            )
+        };
+        let origin = coordinate(-12.0..12.0);
+        let direction = coordinate(-1.0..1.0);
+        Ray::new(origin, direction, 0.0)
+    }
+
+    /// Checks that `bvh` finds the same closest hits as testing every object
+    /// in turn.
+    fn assert_hits_match(bvh: &dyn h::Hittable, objects: &[Arc<dyn h::Hittable>], seed: u64) {
+        let list = HittableList {
+            objects: objects.to_vec(),
+        };
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let r = random_ray(&mut rng);
+            let range = 0.001..f64::INFINITY;
+            let expected = list
+                .hit(&r, range.clone(), &mut Independent)
+                .map(|rec| (rec.t, rec.p));
+            let found = bvh
+                .hit(&r, range, &mut Independent)
+                .map(|rec| (rec.t, rec.p));
+            assert_eq!(found, expected, "ray {:?} {:?}", r.origin(), r.direction());
+            hits += expected.is_some() as usize;
+        }
+        // Make sure the rays actually exercise the tree.
+        assert!(hits > 100, "only {} hits", hits);
+    }
+
+    #[test]
+    fn bvh_node_hits_match_list() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let objects = objects(&mut rng);
+        for split_method in [SplitMethod::Sah, SplitMethod::Median] {
+            for max_leaf_size in [1, 4, 32] {
+                let options = BvhOptions {
+                    split_method,
+                    max_leaf_size,
+                    ..Default::default()
+                };
+                let list = HittableList {
+                    objects: objects.clone(),
+                };
+                let bvh = BvhNode::with_options(&list, 0.0..1.0, &options).unwrap();
+                assert_hits_match(&bvh, &objects, 2);
+            }
+        }
+    }
+
+    #[test]
+    fn bvh_node_rejects_empty_and_unbounded_input() {
+        let empty = HittableList::new();
+        assert!(matches!(
+            BvhNode::new(&empty, 0.0..1.0),
+            Err(BvhError::Empty)
+        ));
+
+        let mut list = HittableList::new();
+        list.add(Arc::new(Sphere::new(
+            Point::zeros(),
+            1.0,
+            Arc::new(Lambertian::new(Color::zeros())),
+        )));
+        list.add(Arc::new(HittableList::new()));
+        assert!(matches!(
+            BvhNode::new(&list, 0.0..1.0),
+            Err(BvhError::Unbounded(1))
+        ));
+    }
+
+    #[test]
+    fn linear_bvh_hits_match_list() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let objects = objects(&mut rng);
+        for split_method in [SplitMethod::Sah, SplitMethod::Median] {
+            for max_leaf_size in [1, 4, 32] {
+                let options = BvhOptions {
+                    split_method,
+                    max_leaf_size,
+                    ..Default::default()
+                };
+                let bvh = LinearBvh::with_options(objects.clone(), 0.0..1.0, &options).unwrap();
+                assert_hits_match(&bvh, &objects, 4);
+            }
+        }
+    }
+
+    #[test]
+    fn accelerator_hits_match_list() {
+        let mut rng = SmallRng::seed_from_u64(5);
+        let mut objects = objects(&mut rng);
+        // An unbounded object goes in the plain list next to the tree.
+        objects.push(Arc::new(HittableList::new()));
+        let accelerator = Accelerator::new(objects.clone(), 0.0..1.0, &BvhOptions::default());
+        assert_hits_match(&accelerator, &objects, 6);
     }
 }
@@ -1,17 +1,47 @@
 use crate::ray::Ray;
-use crate::util;
+use crate::sampler::Sampler;
 use crate::vec::{self, Point, Vec};
 use std::ops::Range;
 
+/// The interval over which the shutter is open. Rays get times in it, and
+/// moving objects are bounded over it.
+pub const SHUTTER: Range<f64> = 0.0..1.0;
+
+/// The parameters a scene gives for its camera. The aspect ratio is left to
+/// the image being rendered, see [`CameraSettings::build`].
+#[derive(Clone)]
+pub struct CameraSettings {
+    pub lookfrom: Point,
+    pub lookat: Point,
+    pub vup: Vec,
+    pub vfov: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
+}
+
+impl CameraSettings {
+    pub fn build(&self, aspect_ratio: f64) -> Camera {
+        Camera::new(
+            self.lookfrom,
+            self.lookat,
+            self.vup,
+            self.vfov,
+            aspect_ratio,
+            self.aperture,
+            self.focus_dist,
+        )
+    }
+}
+
 pub struct Camera {
     origin: Point,
     lower_left_corner: Point,
     horizontal: Vec,
     vertical: Vec,
-    w: Point,
     u: Point,
     v: Point,
     lens_radius: f64,
+    viewport_height: f64,
 }
 
 impl Camera {
@@ -45,21 +75,36 @@ impl Camera {
             lower_left_corner,
             horizontal,
             vertical,
-            w,
             u,
             v,
             lens_radius,
+            viewport_height,
         }
     }
 
-    pub fn get_ray(&self, u: f64, v: f64, time_range: Range<f64>) -> Ray {
-        let rd = self.lens_radius * vec::random_in_unit_disk();
+    /// The angle between rays through neighbouring pixels when the image is
+    /// `image_height` pixels high, exact at the center of the image.
+    pub fn pixel_spread(&self, image_height: u32) -> f64 {
+        self.viewport_height / image_height as f64
+    }
+
+    /// The ray through `(u, v)` on the viewport, leaving from a point on the
+    /// lens and at a time in `time_range` that `sampler` picks.
+    pub fn get_ray(
+        &self,
+        u: f64,
+        v: f64,
+        time_range: Range<f64>,
+        sampler: &mut dyn Sampler,
+    ) -> Ray {
+        let rd = self.lens_radius * vec::unit_disk_sample(sampler.get_2d());
         let offset = self.u * rd.x + self.v * rd.y;
+        let time = time_range.start + sampler.get_1d() * (time_range.end - time_range.start);
 
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin - offset,
-            util::random_f64_range(time_range),
+            time,
         )
     }
 }
@@ -2,19 +2,20 @@ use crate::aabb::Aabb;
 use crate::hittable as h;
 use crate::material::Material;
 use crate::ray::Ray;
-use crate::vec::{Point, Vec};
+use crate::sampler::Sampler;
+use crate::vec::{self, Point, Vec};
 use std::f64::consts::PI;
 use std::ops::Range;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub struct Sphere {
     center: Point,
     radius: f64,
-    material: Rc<dyn Material>,
+    material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Point, radius: f64, material: Rc<dyn Material>) -> Self {
+    pub fn new(center: Point, radius: f64, material: Arc<dyn Material>) -> Self {
         Self {
             center,
             radius,
@@ -28,10 +29,17 @@ impl Sphere {
 
         (phi / (2.0 * PI), theta / PI)
     }
+
+    /// The cosine of the half-angle of the cone the sphere fills, seen from
+    /// `distance_squared` away from its center. `None` from inside.
+    fn cos_theta_max(&self, distance_squared: f64) -> Option<f64> {
+        let ratio = self.radius * self.radius / distance_squared;
+        (ratio < 1.0).then(|| (1.0 - ratio).sqrt())
+    }
 }
 
 impl h::Hittable for Sphere {
-    fn hit(&self, r: &Ray, range: Range<f64>) -> Option<h::HitRecord> {
+    fn hit(&self, r: &Ray, range: Range<f64>, _sampler: &mut dyn Sampler) -> Option<h::HitRecord> {
         let oc = r.origin() - self.center;
 
         let a = r.direction().dot(r.direction());
@@ -54,13 +62,18 @@ impl h::Hittable for Sphere {
 
         let outward_normal = (r.at(root) - self.center) / self.radius;
 
-        Some(h::HitRecord::new(
+        let mut rec = h::HitRecord::new(
             root,
             r,
             outward_normal,
             self.material.clone(),
-            Self::get_uv(&outward_normal)
-        ))
+            Self::get_uv(&outward_normal),
+        );
+        // A unit of v spans half a meridian; a unit of u spans a parallel,
+        // which gets shorter towards the poles.
+        let sin_theta = (1.0 - outward_normal.y * outward_normal.y).max(0.0).sqrt();
+        rec.footprint = r.footprint(root) / (PI * self.radius * (2.0 * sin_theta).clamp(1e-3, 1.0));
+        Some(rec)
     }
 
     fn bounding_box(&self, _time_range: Range<f64>) -> Option<Aabb> {
@@ -69,4 +82,28 @@ impl h::Hittable for Sphere {
             self.center + Vec::from_element(self.radius),
         ))
     }
+
+    /// Samples the cone of directions the sphere covers, which is only
+    /// defined from outside it.
+    fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<Vec> {
+        let axis = self.center - origin;
+        let cos_theta_max = self.cos_theta_max(axis.norm_squared())?;
+
+        let u = sampler.get_2d();
+        let z = 1.0 + u.0 * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * u.1;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let (u, v) = vec::orthonormal_basis(&axis.normalize());
+        Some(u * phi.cos() * sin_theta + v * phi.sin() * sin_theta + axis.normalize() * z)
+    }
+
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        let distance_squared = (self.center - r.origin()).norm_squared();
+        match self.cos_theta_max(distance_squared) {
+            Some(cos_theta_max) if self.hit(r, 0.001..f64::INFINITY, sampler).is_some() => {
+                1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+            }
+            _ => 0.0,
+        }
+    }
 }
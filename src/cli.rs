@@ -0,0 +1,369 @@
+use rayt::filter::FilterKind;
+use rayt::image_writer::Format;
+use rayt::integrator::IntegratorKind;
+use rayt::sampler::SamplerKind;
+use rayt::tonemap::ToneMap;
+use rayt::vec::Point;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const HELP: &str = "\
+Render a scene with the rayt path tracer.
+
+USAGE:
+    rayt [render] [SCENE] [OPTIONS]
+
+SCENE is the name of a built-in scene or the path of a .rayt scene file
+(default: random).
+
+OPTIONS:
+    -s, --scene <SCENE>        Scene to render, same as the positional SCENE
+    -w, --width <PIXELS>       Image width [default: 400]
+    -a, --aspect-ratio <RATIO> Width over height, as `1.5` or `3:2` [default: 3:2]
+    -n, --spp <N>              Samples per pixel, the most any pixel takes with
+                               --error-threshold [default: 100]
+    -e, --error-threshold <E>  Let pixels stop sampling once the standard error
+                               of their mean is at most E times the mean,
+                               as in 0.01 for 1% [default: off]
+        --sample-map <PATH>    Also write how many samples each pixel took, as
+                               a gray image with white at --spp
+    -d, --max-depth <N>        Maximum number of bounces [default: 50]
+        --rr-depth <N>         Bounces before Russian roulette may end a path
+                               [default: 3]
+    -i, --integrator <NAME>    What to draw: path (the path traced image), or
+                               the debug views normals, uv, depth, ao
+                               (ambient occlusion) and bvh-cost (a heatmap of
+                               intersection tests) [default: path]
+        --sampler <NAME>       Where sample positions come from: independent,
+                               stratified, halton or sobol (Owen scrambled)
+                               [default: sobol]
+        --filter <NAME>        How samples are weighted into the pixels around
+                               them: box, tent, gaussian, mitchell or lanczos
+                               [default: box]
+        --filter-radius <PIXELS>
+                               Filter radius [default: 0.5 for box, 1 for
+                               tent, 1.5 for gaussian, 2 for mitchell, 3 for
+                               lanczos]
+        --lookfrom <X,Y,Z>     Override the camera position
+        --lookat <X,Y,Z>       Override the point the camera looks at
+    -o, --output <PATH>        Where to write the image, `-` for stdout
+                               [default: image.png]
+    -f, --format <FORMAT>      Image format: png, png16 (16 bits per channel),
+                               ppm (binary P6), ppm-ascii (P3), or the high
+                               dynamic range pfm, hdr (Radiance) and exr
+                               [default: from the output extension, otherwise png]
+        --tonemap <OPERATOR>   How png and ppm output compress highlights:
+                               clamp, reinhard or aces [default: clamp]
+        --exposure <STOPS>     Exposure adjustment applied before tone mapping
+                               [default: 0]
+    -p, --progressive          Render one sample per pixel at a time, saving the
+                               image so far now and then and when interrupted
+        --snapshot-passes <N>  With --progressive, save every N samples per
+                               pixel [default: off]
+        --snapshot-seconds <S> With --progressive, save every S seconds
+                               [default: 10]
+    -j, --threads <N>          Number of render threads [default: all cores]
+        --seed <N>             Seed for scene generation and sampling; the same
+                               seed gives the same image [default: 0]
+    -h, --help                 Print this help
+";
+
+/// Every option taking a value, as matched in [`parse`].
+const OPTIONS: &[&str] = &[
+    "-s",
+    "--scene",
+    "-w",
+    "--width",
+    "-a",
+    "--aspect-ratio",
+    "-n",
+    "--spp",
+    "-e",
+    "--error-threshold",
+    "--sample-map",
+    "-d",
+    "--max-depth",
+    "--rr-depth",
+    "-i",
+    "--integrator",
+    "--sampler",
+    "--filter",
+    "--filter-radius",
+    "--lookfrom",
+    "--lookat",
+    "-o",
+    "--output",
+    "-f",
+    "--format",
+    "--tonemap",
+    "--exposure",
+    "--snapshot-passes",
+    "--snapshot-seconds",
+    "-j",
+    "--threads",
+    "--seed",
+];
+
+/// The largest image, in pixels, that can be rendered: 16384 by 16384.
+const MAX_PIXELS: u64 = 1 << 28;
+
+pub enum SceneSource {
+    Builtin(String),
+    File(PathBuf),
+}
+
+pub struct Options {
+    pub scene: SceneSource,
+    pub width: u32,
+    pub aspect_ratio: f64,
+    pub samples_per_pixel: u32,
+    pub error_threshold: Option<f64>,
+    pub sample_map: Option<PathBuf>,
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+    pub integrator: IntegratorKind,
+    pub sampler: SamplerKind,
+    pub filter: FilterKind,
+    /// `None` leaves the filter's default.
+    pub filter_radius: Option<f64>,
+    pub lookfrom: Option<Point>,
+    pub lookat: Option<Point>,
+    /// `None` writes to stdout.
+    pub output: Option<PathBuf>,
+    pub format: Format,
+    pub tone_map: ToneMap,
+    pub exposure: f64,
+    pub threads: Option<usize>,
+    pub progressive: bool,
+    pub snapshot_passes: Option<u32>,
+    pub snapshot_interval: Option<Duration>,
+    pub seed: u64,
+}
+
+impl Options {
+    pub fn image_height(&self) -> u32 {
+        ((self.width as f64 / self.aspect_ratio) as u32).max(1)
+    }
+}
+
+pub enum Command {
+    Render(Box<Options>),
+    Help,
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}` for `{}`", value, option))
+}
+
+fn parse_point(option: &str, value: &str) -> Result<Point, String> {
+    let coords = value
+        .split(',')
+        .map(|x| parse_number(option, x.trim()))
+        .collect::<Result<Vec<f64>, _>>()?;
+    match coords.as_slice() {
+        [x, y, z] => Ok(Point::new(*x, *y, *z)),
+        _ => Err(format!(
+            "`{}` expects three comma separated numbers",
+            option
+        )),
+    }
+}
+
+fn parse_aspect_ratio(option: &str, value: &str) -> Result<f64, String> {
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => parse_number::<f64>(option, w)? / parse_number::<f64>(option, h)?,
+        None => parse_number(option, value)?,
+    };
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(format!("invalid value `{}` for `{}`", value, option))
+    }
+}
+
+fn positive<T: Default + PartialOrd>(option: &str, value: T) -> Result<T, String> {
+    if value > T::default() {
+        Ok(value)
+    } else {
+        Err(format!("`{}` must be greater than zero", option))
+    }
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) == Some("render") {
+        args.next();
+    }
+
+    let mut scene = None;
+    let mut format = None;
+    let mut options = Options {
+        scene: SceneSource::Builtin("random".to_owned()),
+        width: 400,
+        aspect_ratio: 3.0 / 2.0,
+        samples_per_pixel: 100,
+        error_threshold: None,
+        sample_map: None,
+        max_depth: 50,
+        roulette_depth: 3,
+        integrator: IntegratorKind::Path,
+        sampler: SamplerKind::Sobol,
+        filter: FilterKind::Box,
+        filter_radius: None,
+        lookfrom: None,
+        lookat: None,
+        output: Some(PathBuf::from("image.png")),
+        format: Format::Png,
+        tone_map: ToneMap::Clamp,
+        exposure: 0.0,
+        threads: None,
+        progressive: false,
+        snapshot_passes: None,
+        snapshot_interval: Some(Duration::from_secs(10)),
+        seed: 0,
+    };
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            if scene.replace(arg).is_some() {
+                return Err("more than one scene given".to_owned());
+            }
+            continue;
+        }
+
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => {
+                (option.to_owned(), Some(value.to_owned()))
+            }
+            _ => (arg, None),
+        };
+        if option == "-h" || option == "--help" {
+            return Ok(Command::Help);
+        }
+        if option == "-p" || option == "--progressive" {
+            if inline_value.is_some() {
+                return Err(format!("`{}` takes no value", option));
+            }
+            options.progressive = true;
+            continue;
+        }
+        if !OPTIONS.contains(&option.as_str()) {
+            return Err(format!("unknown option `{}`", option));
+        }
+        let value = match inline_value.or_else(|| args.next()) {
+            Some(value) => value,
+            None => return Err(format!("`{}` needs a value", option)),
+        };
+
+        match option.as_str() {
+            "-s" | "--scene" => {
+                if scene.replace(value).is_some() {
+                    return Err("more than one scene given".to_owned());
+                }
+            }
+            "-w" | "--width" => options.width = positive(&option, parse_number(&option, &value)?)?,
+            "-a" | "--aspect-ratio" => options.aspect_ratio = parse_aspect_ratio(&option, &value)?,
+            "-n" | "--spp" => {
+                options.samples_per_pixel = positive(&option, parse_number(&option, &value)?)?
+            }
+            "-e" | "--error-threshold" => {
+                options.error_threshold = Some(positive(&option, parse_number(&option, &value)?)?)
+            }
+            "--sample-map" => options.sample_map = Some(PathBuf::from(value)),
+            "-d" | "--max-depth" => options.max_depth = parse_number(&option, &value)?,
+            "--rr-depth" => options.roulette_depth = parse_number(&option, &value)?,
+            "-i" | "--integrator" => match IntegratorKind::from_name(&value) {
+                Some(i) => options.integrator = i,
+                None => return Err(format!("unknown integrator `{}`", value)),
+            },
+            "--sampler" => match SamplerKind::from_name(&value) {
+                Some(s) => options.sampler = s,
+                None => return Err(format!("unknown sampler `{}`", value)),
+            },
+            "--filter" => match FilterKind::from_name(&value) {
+                Some(f) => options.filter = f,
+                None => return Err(format!("unknown filter `{}`", value)),
+            },
+            "--filter-radius" => {
+                options.filter_radius = Some(positive(&option, parse_number(&option, &value)?)?)
+            }
+            "--lookfrom" => options.lookfrom = Some(parse_point(&option, &value)?),
+            "--lookat" => options.lookat = Some(parse_point(&option, &value)?),
+            "-o" | "--output" => {
+                options.output = if value == "-" {
+                    None
+                } else {
+                    Some(PathBuf::from(value))
+                }
+            }
+            "-f" | "--format" => match Format::from_name(&value) {
+                Some(f) => format = Some(f),
+                None => return Err(format!("unknown image format `{}`", value)),
+            },
+            "--tonemap" => match ToneMap::from_name(&value) {
+                Some(t) => options.tone_map = t,
+                None => return Err(format!("unknown tone mapping operator `{}`", value)),
+            },
+            "--exposure" => options.exposure = parse_number(&option, &value)?,
+            "-j" | "--threads" => {
+                options.threads = Some(positive(&option, parse_number(&option, &value)?)?)
+            }
+            "--snapshot-passes" => {
+                options.snapshot_passes = Some(positive(&option, parse_number(&option, &value)?)?)
+            }
+            "--snapshot-seconds" => {
+                let seconds = positive(&option, parse_number(&option, &value)?)?;
+                options.snapshot_interval = Some(
+                    Duration::try_from_secs_f64(seconds)
+                        .map_err(|_| format!("invalid value `{}` for `{}`", value, option))?,
+                )
+            }
+            "--seed" => options.seed = parse_number(&option, &value)?,
+            _ => unreachable!(),
+        }
+    }
+
+    if let Some(scene) = scene {
+        options.scene = if rayt::scenes::NAMES.contains(&scene.as_str()) {
+            SceneSource::Builtin(scene)
+        } else {
+            SceneSource::File(PathBuf::from(scene))
+        };
+    }
+
+    options.format = match (format, &options.output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => path
+            .extension()
+            .and_then(|ext| Format::from_extension(&ext.to_string_lossy()))
+            .unwrap_or(Format::Png),
+        (None, None) => Format::Png,
+    };
+
+    let (width, height) = (options.width, options.image_height());
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(format!(
+            "a {}x{} image is too large, the most is {} pixels",
+            width, height, MAX_PIXELS
+        ));
+    }
+
+    if let Some(radius) = options.filter_radius {
+        // Past the image size a wider filter changes nothing but the cost.
+        let size = options.width.max(options.image_height());
+        if !radius.is_finite() || radius > size as f64 {
+            return Err(format!(
+                "`--filter-radius` must be at most the image size ({} pixels)",
+                size
+            ));
+        }
+    }
+
+    if options.progressive && options.output.is_none() {
+        return Err("`--progressive` needs an output file, not stdout".to_owned());
+    }
+
+    Ok(Command::Render(Box::new(options)))
+}
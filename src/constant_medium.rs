@@ -0,0 +1,109 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::texture::Texture;
+use crate::vec::{self, Color};
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// A volume of uniform density filling `boundary`, like smoke or fog. Rays
+/// travelling through it scatter after an exponentially distributed
+/// distance. The boundary has to be closed and convex, since only its first
+/// entry and exit points are looked at.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+/// The density given to a [`ConstantMedium`] was zero, negative or not
+/// finite.
+#[derive(Debug)]
+pub struct DensityError(pub f64);
+
+impl fmt::Display for DensityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "density must be positive and finite, not {}", self.0)
+    }
+}
+
+impl std::error::Error for DensityError {}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        color: Color,
+    ) -> Result<Self, DensityError> {
+        Self::from_material(boundary, density, Arc::new(Isotropic::new(color)))
+    }
+
+    pub fn from_texture(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        albedo: Arc<dyn Texture>,
+    ) -> Result<Self, DensityError> {
+        Self::from_material(boundary, density, Arc::new(Isotropic::from_texture(albedo)))
+    }
+
+    fn from_material(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> Result<Self, DensityError> {
+        if !(density.is_finite() && density > 0.0) {
+            return Err(DensityError(density));
+        }
+        Ok(Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        })
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, range: Range<f64>, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        // Find where the ray enters and leaves the boundary, wherever the
+        // ray starts, then clip that span to `range`.
+        let enter = self
+            .boundary
+            .hit(r, f64::NEG_INFINITY..f64::INFINITY, sampler)?;
+        let exit = self
+            .boundary
+            .hit(r, enter.t + 0.0001..f64::INFINITY, sampler)?;
+
+        let t_enter = enter.t.max(range.start).max(0.0);
+        let t_exit = exit.t.min(range.end);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = r.direction().norm();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * (1.0 - sampler.get_1d()).ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        // The normal and uv are arbitrary: isotropic scattering ignores the
+        // normal, and the medium has no surface to map a texture on.
+        let mut rec = HitRecord::new(
+            t,
+            r,
+            vec::Vec::new(1.0, 0.0, 0.0),
+            self.phase_function.clone(),
+            (0.0, 0.0),
+        );
+        rec.front_face = true;
+        Some(rec)
+    }
+
+    fn bounding_box(&self, time_range: Range<f64>) -> Option<Aabb> {
+        self.boundary.bounding_box(time_range)
+    }
+}
@@ -0,0 +1,287 @@
+//! Reconstruction filters, weighing how much a sample counts towards the
+//! pixels around it. Each pixel ends up the weighted average of the samples
+//! within the filter's radius of its center, so a sample taken near the
+//! edge of a pixel also counts towards its neighbours.
+
+use std::f64::consts::PI;
+
+pub trait Filter: Send + Sync {
+    /// How far from a pixel center, in pixels along each axis, samples
+    /// still count towards the pixel.
+    fn radius(&self) -> f64;
+
+    /// The weight of a sample `(dx, dy)` pixels from a pixel center. May be
+    /// negative for filters that sharpen.
+    fn evaluate(&self, dx: f64, dy: f64) -> f64;
+}
+
+/// Every sample within the radius counts the same. With a radius of half
+/// a pixel, pixels average exactly the samples taken inside them.
+pub struct BoxFilter {
+    pub radius: f64,
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        if dx.abs() <= self.radius && dy.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Weights falling off linearly to zero at the radius.
+pub struct Tent {
+    pub radius: f64,
+}
+
+impl Filter for Tent {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        (self.radius - dx.abs()).max(0.0) * (self.radius - dy.abs()).max(0.0)
+    }
+}
+
+/// A Gaussian of standard deviation `sigma`, shifted down to reach zero at
+/// the radius. A third of the radius leaves little of the curve cut off.
+pub struct Gaussian {
+    pub radius: f64,
+    pub sigma: f64,
+}
+
+impl Gaussian {
+    fn gaussian(&self, x: f64) -> f64 {
+        let g = |x: f64| (-x * x / (2.0 * self.sigma * self.sigma)).exp();
+        (g(x) - g(self.radius)).max(0.0)
+    }
+}
+
+impl Filter for Gaussian {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.gaussian(dx) * self.gaussian(dy)
+    }
+}
+
+/// Mitchell and Netravali's cubic, stretched over the radius. `b` and `c`
+/// trade blurring against ringing; both at 1/3 is their recommendation.
+pub struct Mitchell {
+    pub radius: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+impl Mitchell {
+    /// The cubic over `-2..2`.
+    fn mitchell(&self, x: f64) -> f64 {
+        let (b, c) = (self.b, self.c);
+        let x = x.abs();
+        if x < 1.0 {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b))
+                / 6.0
+        } else if x < 2.0 {
+            ((-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Filter for Mitchell {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        let scale = 2.0 / self.radius;
+        self.mitchell(dx * scale) * self.mitchell(dy * scale)
+    }
+}
+
+/// A sinc windowed by a wider sinc that reaches zero at the radius. Keeps
+/// the most detail of these, at the price of ringing around edges.
+pub struct Lanczos {
+    pub radius: f64,
+}
+
+impl Lanczos {
+    fn lanczos(&self, x: f64) -> f64 {
+        if x.abs() >= self.radius {
+            0.0
+        } else {
+            sinc(x) * sinc(x / self.radius)
+        }
+    }
+}
+
+impl Filter for Lanczos {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.lanczos(dx) * self.lanczos(dy)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// The filters the command line can pick by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterKind {
+    pub const NAMES: &'static [&'static str] = &["box", "tent", "gaussian", "mitchell", "lanczos"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "box" => Some(FilterKind::Box),
+            "tent" => Some(FilterKind::Tent),
+            "gaussian" => Some(FilterKind::Gaussian),
+            "mitchell" => Some(FilterKind::Mitchell),
+            "lanczos" => Some(FilterKind::Lanczos),
+            _ => None,
+        }
+    }
+
+    /// The radius the filter gets unless told otherwise.
+    pub fn default_radius(self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+
+    /// Builds the filter with `radius`, or its default radius if `None`.
+    pub fn build(self, radius: Option<f64>) -> Box<dyn Filter> {
+        let radius = radius.unwrap_or_else(|| self.default_radius());
+        match self {
+            FilterKind::Box => Box::new(BoxFilter { radius }),
+            FilterKind::Tent => Box::new(Tent { radius }),
+            FilterKind::Gaussian => Box::new(Gaussian {
+                radius,
+                sigma: radius / 3.0,
+            }),
+            FilterKind::Mitchell => Box::new(Mitchell {
+                radius,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+            FilterKind::Lanczos => Box::new(Lanczos { radius }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [FilterKind; 5] = [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ];
+
+    #[test]
+    fn names_round_trip() {
+        for (&name, kind) in FilterKind::NAMES.iter().zip(KINDS) {
+            assert_eq!(FilterKind::from_name(name), Some(kind));
+            assert_eq!(FilterKind::from_name(&name.to_uppercase()), Some(kind));
+        }
+        assert_eq!(FilterKind::from_name("sinc"), None);
+    }
+
+    #[test]
+    fn weights_are_symmetric_and_vanish_past_the_radius() {
+        for kind in KINDS {
+            for radius in [None, Some(0.75), Some(2.5)] {
+                let filter = kind.build(radius);
+                let r = filter.radius();
+                assert_eq!(r, radius.unwrap_or_else(|| kind.default_radius()));
+                assert!(filter.evaluate(0.0, 0.0) > 0.0, "{:?}", kind);
+                for (dx, dy) in [(0.3 * r, 0.1 * r), (0.7 * r, -0.4 * r), (0.9 * r, 0.9 * r)] {
+                    let w = filter.evaluate(dx, dy);
+                    assert_eq!(filter.evaluate(-dx, dy), w);
+                    assert_eq!(filter.evaluate(dx, -dy), w);
+                    assert_eq!(filter.evaluate(dy, dx), w);
+                }
+                assert_eq!(filter.evaluate(1.01 * r, 0.0), 0.0, "{:?}", kind);
+                assert_eq!(filter.evaluate(0.0, -1.5 * r), 0.0, "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn known_weights() {
+        let box_filter = FilterKind::Box.build(None);
+        assert_eq!(box_filter.evaluate(0.5, -0.5), 1.0);
+        assert_eq!(box_filter.evaluate(0.51, 0.0), 0.0);
+
+        let tent = FilterKind::Tent.build(None);
+        assert_eq!(tent.evaluate(0.5, 0.0), 0.5);
+
+        // Mitchell's cubic with b = c = 1/3 is 8/9 at the center and zero at
+        // its radius.
+        let mitchell = FilterKind::Mitchell.build(None);
+        assert!((mitchell.evaluate(0.0, 0.0) - 64.0 / 81.0).abs() < 1e-12);
+        assert!(mitchell.evaluate(2.0, 0.0).abs() < 1e-12);
+
+        // The sinc is zero at whole pixels.
+        let lanczos = FilterKind::Lanczos.build(None);
+        assert_eq!(lanczos.evaluate(0.0, 0.0), 1.0);
+        assert!(lanczos.evaluate(1.0, 0.0).abs() < 1e-12);
+        assert!(lanczos.evaluate(1.5, 0.0) < 0.0);
+    }
+
+    #[test]
+    fn gaussian_sigma_follows_the_radius() {
+        let gaussian = |radius| Gaussian {
+            radius,
+            sigma: radius / 3.0,
+        };
+        // The default radius keeps the usual sigma of half a pixel.
+        let default = FilterKind::Gaussian.build(None);
+        let expected = gaussian(1.5);
+        let wide = FilterKind::Gaussian.build(Some(3.0));
+        for x in [0.0, 0.25, 0.8, 1.2] {
+            assert_eq!(default.evaluate(x, 0.0), expected.evaluate(x, 0.0));
+            // Twice the radius stretches the curve to twice the width.
+            assert!((wide.evaluate(2.0 * x, 0.0) - default.evaluate(x, 0.0)).abs() < 1e-12);
+        }
+        assert_eq!(expected.sigma, 0.5);
+    }
+}
@@ -1,17 +1,21 @@
 use crate::aabb::Aabb;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec;
 use std::ops::Range;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub struct HitRecord {
     pub p: vec::Point,
     pub normal: vec::Vec,
     pub t: f64,
     pub uv: (f64, f64),
+    /// About how many uv units the ray's cone covers around `uv`, for
+    /// texture filtering. Zero when the shape doesn't estimate it.
+    pub footprint: f64,
     pub front_face: bool,
-    pub material: Rc<dyn Material>,
+    pub material: Arc<dyn Material>,
 }
 
 impl HitRecord {
@@ -19,7 +23,7 @@ impl HitRecord {
         t: f64,
         r: &Ray,
         normal: vec::Vec,
-        material: Rc<dyn Material>,
+        material: Arc<dyn Material>,
         uv: (f64, f64),
     ) -> Self {
         let (normal, front_face) = if r.direction().dot(&normal) < 0.0 {
@@ -33,13 +37,53 @@ impl HitRecord {
             normal,
             t,
             uv,
+            footprint: 0.0,
             front_face,
             material,
         }
     }
 }
 
-pub trait Hittable {
-    fn hit(&self, r: &Ray, range: Range<f64>) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, r: &Ray, range: Range<f64>, sampler: &mut dyn Sampler) -> Option<HitRecord>;
     fn bounding_box(&self, time_range: Range<f64>) -> Option<Aabb>;
+
+    /// [`hit`](Self::hit), adding the number of bounding boxes and
+    /// primitives tested to `tests`. Only used to draw cost heatmaps, so
+    /// containers forward it and everything else counts as one test.
+    fn hit_counting(
+        &self,
+        r: &Ray,
+        range: Range<f64>,
+        tests: &mut usize,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord> {
+        *tests += 1;
+        self.hit(r, range, sampler)
+    }
+
+    /// Picks a direction from `origin` towards the object, for sampling it as
+    /// a light. `None` if the object can't be sampled from there.
+    fn sample_towards(&self, _origin: &vec::Point, _sampler: &mut dyn Sampler) -> Option<vec::Vec> {
+        None
+    }
+
+    /// The density per unit solid angle with which
+    /// [`sample_towards`](Self::sample_towards) picks the direction of `r`
+    /// from its origin. Zero if `r` misses the object.
+    fn pdf_towards(&self, _r: &Ray, _sampler: &mut dyn Sampler) -> f64 {
+        0.0
+    }
+}
+
+/// The solid angle density of a uniformly sampled point on a surface of
+/// `area`, seen along `r` which hits it at `t` where the surface has
+/// `normal`.
+pub fn area_to_solid_angle_pdf(r: &Ray, t: f64, normal: &vec::Vec, area: f64) -> f64 {
+    let length_squared = r.direction().norm_squared();
+    let cosine = r.direction().dot(normal).abs() / length_squared.sqrt();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    t * t * length_squared / (cosine * area)
 }
@@ -1,11 +1,14 @@
 use crate::aabb::Aabb;
 use crate::hittable as h;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec;
 use std::ops::Range;
-use std::rc::Rc;
+use std::sync::Arc;
 
+#[derive(Default)]
 pub struct HittableList {
-    pub objects: Vec<Rc<dyn h::Hittable>>,
+    pub objects: Vec<Arc<dyn h::Hittable>>,
 }
 
 impl HittableList {
@@ -15,15 +18,15 @@ impl HittableList {
         }
     }
 
-    pub fn add(&mut self, obj: Rc<dyn h::Hittable>) {
+    pub fn add(&mut self, obj: Arc<dyn h::Hittable>) {
         self.objects.push(obj);
     }
 }
 
 impl h::Hittable for HittableList {
-    fn hit(&self, r: &Ray, range: Range<f64>) -> Option<h::HitRecord> {
-        let f = |(closest, rec), obj: &Rc<dyn h::Hittable>| {
-            if let Some(temp_rec) = obj.hit(r, range.start..closest) {
+    fn hit(&self, r: &Ray, range: Range<f64>, sampler: &mut dyn Sampler) -> Option<h::HitRecord> {
+        let f = |(closest, rec), obj: &Arc<dyn h::Hittable>| {
+            if let Some(temp_rec) = obj.hit(r, range.start..closest, sampler) {
                 (temp_rec.t, Some(temp_rec))
             } else {
                 (closest, rec)
@@ -33,6 +36,24 @@ impl h::Hittable for HittableList {
         self.objects.iter().fold((range.end, None), f).1
     }
 
+    fn hit_counting(
+        &self,
+        r: &Ray,
+        range: Range<f64>,
+        tests: &mut usize,
+        sampler: &mut dyn Sampler,
+    ) -> Option<h::HitRecord> {
+        let mut closest = None;
+        let mut end = range.end;
+        for object in &self.objects {
+            if let Some(rec) = object.hit_counting(r, range.start..end, tests, sampler) {
+                end = rec.t;
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
     fn bounding_box(&self, time_range: Range<f64>) -> Option<Aabb> {
         if self.objects.is_empty() {
             None
@@ -56,4 +77,25 @@ impl h::Hittable for HittableList {
             bbox
         }
     }
+
+    /// Samples one of the objects, chosen uniformly.
+    fn sample_towards(&self, origin: &vec::Point, sampler: &mut dyn Sampler) -> Option<vec::Vec> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        let index = (sampler.get_1d() * self.objects.len() as f64) as usize;
+        self.objects[index.min(self.objects.len() - 1)].sample_towards(origin, sampler)
+    }
+
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_towards(r, sampler))
+            .sum();
+        sum / self.objects.len() as f64
+    }
 }
@@ -0,0 +1,644 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// A decoded image: linear RGBA in `0.0..=1.0` (HDR images go above 1.0),
+/// top row first. Alpha is straight, not premultiplied, and 1.0 for
+/// formats without it.
+#[derive(Clone, Debug)]
+pub struct ImageBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl ImageBuffer {
+    /// Panics if the image is empty or `pixels` does not hold
+    /// `width * height` pixels.
+    pub fn new(width: usize, height: usize, pixels: Vec<[f32; 4]>) -> Self {
+        assert!(width > 0 && height > 0, "empty image");
+        assert_eq!(pixels.len(), width * height);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [f32; 4] {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn pixels(&self) -> &[[f32; 4]] {
+        &self.pixels
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(io::Error),
+    Png(png::DecodingError),
+    Jpeg(jpeg_decoder::Error),
+    /// A malformed or unsupported Radiance file.
+    Hdr(String),
+    /// An image with no pixels, or more than [`MAX_DIMENSION`] wide or
+    /// tall, or more than [`MAX_PIXELS`] in all.
+    Size(usize, usize),
+    UnknownFormat,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "{}", err),
+            DecodeError::Png(err) => write!(f, "invalid PNG: {}", err),
+            DecodeError::Jpeg(err) => write!(f, "invalid JPEG: {}", err),
+            DecodeError::Hdr(message) => write!(f, "invalid Radiance HDR: {}", message),
+            DecodeError::Size(width, height) => {
+                write!(f, "unsupported image size {}x{}", width, height)
+            }
+            DecodeError::UnknownFormat => write!(f, "not a PNG, JPEG or Radiance HDR image"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        DecodeError::Io(err)
+    }
+}
+
+impl From<png::DecodingError> for DecodeError {
+    fn from(err: png::DecodingError) -> Self {
+        DecodeError::Png(err)
+    }
+}
+
+impl From<jpeg_decoder::Error> for DecodeError {
+    fn from(err: jpeg_decoder::Error) -> Self {
+        DecodeError::Jpeg(err)
+    }
+}
+
+/// Decodes some image file format into linear RGBA.
+pub trait ImageDecoder {
+    fn decode(&self, reader: &mut dyn Read) -> Result<ImageBuffer, DecodeError>;
+}
+
+/// Picks a decoder from the file name, falling back on the first bytes of
+/// the file, and decodes it.
+pub fn load(path: &Path) -> Result<ImageBuffer, DecodeError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let decoder = match path
+        .extension()
+        .and_then(|ext| decoder_for_extension(&ext.to_string_lossy()))
+    {
+        Some(decoder) => decoder,
+        None => decoder_for_signature(reader.fill_buf()?).ok_or(DecodeError::UnknownFormat)?,
+    };
+    decoder.decode(&mut reader)
+}
+
+pub fn decoder_for_extension(extension: &str) -> Option<Box<dyn ImageDecoder>> {
+    match extension.to_ascii_lowercase().as_str() {
+        "png" => Some(Box::new(Png)),
+        "jpg" | "jpeg" => Some(Box::new(Jpeg)),
+        "hdr" | "pic" => Some(Box::new(Hdr)),
+        _ => None,
+    }
+}
+
+pub fn decoder_for_signature(start: &[u8]) -> Option<Box<dyn ImageDecoder>> {
+    if start.starts_with(b"\x89PNG") {
+        Some(Box::new(Png))
+    } else if start.starts_with(&[0xff, 0xd8]) {
+        Some(Box::new(Jpeg))
+    } else if start.starts_with(b"#?") {
+        Some(Box::new(Hdr))
+    } else {
+        None
+    }
+}
+
+/// The widest or tallest image the decoders accept.
+pub const MAX_DIMENSION: usize = 1 << 16;
+
+/// The most pixels the decoders accept, 2 GiB once decoded: room for a
+/// 16384 by 8192 texture.
+pub const MAX_PIXELS: usize = 1 << 27;
+
+/// Rejects a `width` by `height` image, as declared by a header, that is
+/// empty or too big to decode, before anything gets allocated for it.
+fn check_size(width: usize, height: usize) -> Result<(), DecodeError> {
+    let fits = (1..=MAX_DIMENSION).contains(&width)
+        && (1..=MAX_DIMENSION).contains(&height)
+        && width <= MAX_PIXELS / height;
+    if fits {
+        Ok(())
+    } else {
+        Err(DecodeError::Size(width, height))
+    }
+}
+
+/// The transfer curve 8 and 16 bit images were encoded with.
+#[derive(Clone, Copy)]
+enum Transfer {
+    Srgb,
+    /// Plain power law; the value is the encoding exponent, as stored in a
+    /// PNG `gAMA` chunk.
+    Gamma(f32),
+}
+
+impl Transfer {
+    fn to_linear(self, x: f32) -> f32 {
+        match self {
+            Transfer::Srgb if x <= 0.04045 => x / 12.92,
+            Transfer::Srgb => ((x + 0.055) / 1.055).powf(2.4),
+            Transfer::Gamma(gamma) => x.powf(1.0 / gamma),
+        }
+    }
+
+    fn table(self) -> [f32; 256] {
+        let mut table = [0.0; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = self.to_linear(i as f32 / 255.0);
+        }
+        table
+    }
+}
+
+/// Every color type and bit depth, with `tRNS` transparency turned into
+/// alpha. Colors are assumed to be sRGB unless the file gives only a gamma.
+pub struct Png;
+
+impl ImageDecoder for Png {
+    fn decode(&self, reader: &mut dyn Read) -> Result<ImageBuffer, DecodeError> {
+        let mut decoder = png::Decoder::new(reader);
+        // Expands palettes, low bit depths and tRNS, leaving 8 or 16 bit
+        // gray, gray + alpha, RGB or RGBA.
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        check_size(reader.info().width as usize, reader.info().height as usize)?;
+
+        let transfer = match (reader.info().srgb, reader.info().source_gamma) {
+            (None, Some(gamma)) => Transfer::Gamma(gamma.into_value()),
+            _ => Transfer::Srgb,
+        };
+
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        let (width, height) = (info.width as usize, info.height as usize);
+
+        let channels = info.color_type.samples();
+        let sample_bytes = if info.bit_depth == png::BitDepth::Sixteen {
+            2
+        } else {
+            1
+        };
+        let samples: Vec<f32> = match info.bit_depth {
+            png::BitDepth::Sixteen => data
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.0)
+                .collect(),
+            _ => data.iter().map(|&b| b as f32 / 255.0).collect(),
+        };
+
+        let mut pixels = Vec::new();
+        for row in samples
+            .chunks_exact(info.line_size / sample_bytes)
+            .take(height)
+        {
+            for p in row[..width * channels].chunks_exact(channels) {
+                let color = |x: f32| transfer.to_linear(x);
+                pixels.push(match *p {
+                    [g] => [color(g), color(g), color(g), 1.0],
+                    [g, a] => [color(g), color(g), color(g), a],
+                    [r, g, b] => [color(r), color(g), color(b), 1.0],
+                    [r, g, b, a] => [color(r), color(g), color(b), a],
+                    _ => unreachable!("EXPAND leaves at most four channels"),
+                });
+            }
+        }
+        Ok(ImageBuffer::new(width, height, pixels))
+    }
+}
+
+/// Baseline and progressive JPEG, assumed to be sRGB.
+pub struct Jpeg;
+
+impl ImageDecoder for Jpeg {
+    fn decode(&self, reader: &mut dyn Read) -> Result<ImageBuffer, DecodeError> {
+        let mut decoder = jpeg_decoder::Decoder::new(reader);
+        decoder.read_info()?;
+        let info = decoder.info().expect("known after reading the header");
+        let (width, height) = (info.width as usize, info.height as usize);
+        check_size(width, height)?;
+        let data = decoder.decode()?;
+
+        let table = Transfer::Srgb.table();
+        let gray = |g: f32| [g, g, g, 1.0];
+        let pixels = match info.pixel_format {
+            jpeg_decoder::PixelFormat::L8 => {
+                data.iter().map(|&g| gray(table[g as usize])).collect()
+            }
+            // The decoder hands 16 bit samples back in native byte order.
+            jpeg_decoder::PixelFormat::L16 => data
+                .chunks_exact(2)
+                .map(|b| {
+                    gray(
+                        Transfer::Srgb.to_linear(u16::from_ne_bytes([b[0], b[1]]) as f32 / 65535.0),
+                    )
+                })
+                .collect(),
+            jpeg_decoder::PixelFormat::RGB24 => data
+                .chunks_exact(3)
+                .map(|p| {
+                    [
+                        table[p[0] as usize],
+                        table[p[1] as usize],
+                        table[p[2] as usize],
+                        1.0,
+                    ]
+                })
+                .collect(),
+            // Adobe writes CMYK JPEGs inverted, which is what the decoder
+            // hands back, so each channel is already `1 - ink`.
+            jpeg_decoder::PixelFormat::CMYK32 => data
+                .chunks_exact(4)
+                .map(|p| {
+                    let k = p[3] as f32 / 255.0;
+                    let f = |c: u8| Transfer::Srgb.to_linear(c as f32 / 255.0 * k);
+                    [f(p[0]), f(p[1]), f(p[2]), 1.0]
+                })
+                .collect(),
+        };
+        Ok(ImageBuffer::new(width, height, pixels))
+    }
+}
+
+/// Radiance RGBE files, flat or with the run-length encoding of current
+/// Radiance versions, in the usual `-Y H +X W` orientation. The data is
+/// already linear.
+pub struct Hdr;
+
+impl Hdr {
+    fn read_line(reader: &mut dyn Read) -> Result<String, DecodeError> {
+        let mut line = Vec::new();
+        let mut byte = [0];
+        loop {
+            reader.read_exact(&mut byte)?;
+            if byte[0] == b'\n' {
+                break;
+            }
+            line.push(byte[0]);
+            if line.len() > 4096 {
+                return Err(DecodeError::Hdr("header line too long".to_owned()));
+            }
+        }
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    fn resolution(line: &str) -> Option<(usize, usize)> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => Some((width.parse().ok()?, height.parse().ok()?)),
+            _ => None,
+        }
+    }
+
+    /// Reads one scanline of RGBE quadruples into `scanline`.
+    fn read_scanline(reader: &mut dyn Read, scanline: &mut [[u8; 4]]) -> Result<(), DecodeError> {
+        let width = scanline.len();
+        let mut start = [0; 4];
+        reader.read_exact(&mut start)?;
+
+        // New-style run-length encoding stores each component separately.
+        let rle = start[0] == 2 && start[1] == 2 && start[2] & 0x80 == 0;
+        if !rle || !(8..0x8000).contains(&width) {
+            scanline[0] = start;
+            for pixel in &mut scanline[1..] {
+                reader.read_exact(pixel)?;
+            }
+            return Ok(());
+        }
+
+        if ((start[2] as usize) << 8 | start[3] as usize) != width {
+            return Err(DecodeError::Hdr("scanline width mismatch".to_owned()));
+        }
+        for component in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let mut count = [0];
+                reader.read_exact(&mut count)?;
+                let (run, count) = if count[0] > 128 {
+                    (true, count[0] as usize - 128)
+                } else {
+                    (false, count[0] as usize)
+                };
+                if count == 0 || x + count > width {
+                    return Err(DecodeError::Hdr("bad run length".to_owned()));
+                }
+
+                if run {
+                    let mut value = [0];
+                    reader.read_exact(&mut value)?;
+                    for pixel in &mut scanline[x..x + count] {
+                        pixel[component] = value[0];
+                    }
+                } else {
+                    let mut values = vec![0; count];
+                    reader.read_exact(&mut values)?;
+                    for (pixel, value) in scanline[x..x + count].iter_mut().zip(values) {
+                        pixel[component] = value;
+                    }
+                }
+                x += count;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ImageDecoder for Hdr {
+    fn decode(&self, reader: &mut dyn Read) -> Result<ImageBuffer, DecodeError> {
+        let magic = Self::read_line(reader)?;
+        if !magic.starts_with("#?") {
+            return Err(DecodeError::Hdr("missing `#?` signature".to_owned()));
+        }
+        loop {
+            let line = Self::read_line(reader)?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(DecodeError::Hdr(format!("unsupported format `{}`", format)));
+                }
+            }
+        }
+
+        let line = Self::read_line(reader)?;
+        let (width, height) = Self::resolution(&line)
+            .ok_or_else(|| DecodeError::Hdr(format!("unsupported resolution line `{}`", line)))?;
+        check_size(width, height)?;
+
+        // Grown as scanlines arrive, so a truncated file fails before its
+        // whole declared size is allocated.
+        let mut pixels = Vec::new();
+        let mut scanline = vec![[0; 4]; width];
+        for _ in 0..height {
+            Self::read_scanline(reader, &mut scanline)?;
+            pixels.extend(scanline.iter().map(|&[r, g, b, e]| {
+                if e == 0 {
+                    return [0.0, 0.0, 0.0, 1.0];
+                }
+                let scale = (e as i32 - 136) as f32;
+                let f = |m: u8| (m as f32 + 0.5) * scale.exp2();
+                [f(r), f(g), f(b), 1.0]
+            }));
+        }
+        Ok(ImageBuffer::new(width, height, pixels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_writer::{ImageWriter, Rgbe};
+    use crate::render::Framebuffer;
+    use crate::vec::Color;
+
+    fn encode_png(
+        width: u32,
+        height: u32,
+        color: png::ColorType,
+        depth: png::BitDepth,
+        data: &[u8],
+        setup: impl FnOnce(&mut png::Encoder<&mut Vec<u8>>),
+    ) -> Vec<u8> {
+        let mut file = Vec::new();
+        let mut encoder = png::Encoder::new(&mut file, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        setup(&mut encoder);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        file
+    }
+
+    fn decode(decoder: &dyn ImageDecoder, mut file: &[u8]) -> Result<ImageBuffer, DecodeError> {
+        decoder.decode(&mut file)
+    }
+
+    fn assert_close(found: [f32; 4], expected: [f32; 4]) {
+        for (f, e) in found.iter().zip(expected) {
+            assert!((f - e).abs() < 1e-3, "{:?} != {:?}", found, expected);
+        }
+    }
+
+    /// sRGB 128 / 255, linearized.
+    const MID_GRAY: f32 = 0.215_861;
+
+    #[test]
+    fn png_rgb_and_rgba() {
+        let file = encode_png(
+            2,
+            1,
+            png::ColorType::Rgb,
+            png::BitDepth::Eight,
+            &[255, 0, 128, 0, 0, 0],
+            |_| {},
+        );
+        let image = decode(&Png, &file).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_close(image.pixel(0, 0), [1.0, 0.0, MID_GRAY, 1.0]);
+        assert_close(image.pixel(1, 0), [0.0, 0.0, 0.0, 1.0]);
+
+        let file = encode_png(
+            1,
+            2,
+            png::ColorType::Rgba,
+            png::BitDepth::Eight,
+            &[255, 255, 255, 128, 0, 0, 0, 0],
+            |_| {},
+        );
+        let image = decode(&Png, &file).unwrap();
+        assert_eq!((image.width(), image.height()), (1, 2));
+        // Alpha stays linear.
+        assert_close(image.pixel(0, 0), [1.0, 1.0, 1.0, 128.0 / 255.0]);
+        assert_close(image.pixel(0, 1), [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn png_grayscale_16_bit() {
+        let file = encode_png(
+            2,
+            1,
+            png::ColorType::GrayscaleAlpha,
+            png::BitDepth::Sixteen,
+            &[0xff, 0xff, 0x80, 0x00, 0x00, 0x00, 0xff, 0xff],
+            |_| {},
+        );
+        let image = decode(&Png, &file).unwrap();
+        assert_close(image.pixel(0, 0), [1.0, 1.0, 1.0, 0x8000 as f32 / 65535.0]);
+        assert_close(image.pixel(1, 0), [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn png_palette_with_transparency() {
+        // Two bits per pixel: entries 2, 1, 0 and 1.
+        let file = encode_png(
+            4,
+            1,
+            png::ColorType::Indexed,
+            png::BitDepth::Two,
+            &[0b1001_0001],
+            |encoder| {
+                encoder.set_palette(vec![0, 0, 0, 128, 128, 128, 255, 0, 0]);
+                encoder.set_trns(vec![255, 0]);
+            },
+        );
+        let image = decode(&Png, &file).unwrap();
+        assert_close(image.pixel(0, 0), [1.0, 0.0, 0.0, 1.0]);
+        assert_close(image.pixel(1, 0), [MID_GRAY, MID_GRAY, MID_GRAY, 0.0]);
+        assert_close(image.pixel(2, 0), [0.0, 0.0, 0.0, 1.0]);
+        assert_close(image.pixel(3, 0), [MID_GRAY, MID_GRAY, MID_GRAY, 0.0]);
+    }
+
+    #[test]
+    fn png_gamma_without_srgb() {
+        let file = encode_png(
+            1,
+            1,
+            png::ColorType::Grayscale,
+            png::BitDepth::Eight,
+            &[128],
+            |encoder| encoder.set_source_gamma(png::ScaledFloat::new(0.5)),
+        );
+        let g = (128.0f32 / 255.0).powi(2);
+        assert_close(decode(&Png, &file).unwrap().pixel(0, 0), [g, g, g, 1.0]);
+    }
+
+    #[test]
+    fn hdr_round_trips() {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        let colors = [
+            Color::new(1.0, 0.5, 0.25),
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(100.0, 10.0, 1.0),
+            Color::new(0.01, 0.02, 0.03),
+            Color::new(3.0, 3.0, 3.0),
+            Color::new(0.5, 0.0, 2.0),
+        ];
+        framebuffer.pixels_mut().copy_from_slice(&colors);
+        let mut file = Vec::new();
+        Rgbe.write_image(&framebuffer, &mut file).unwrap();
+
+        let image = decode(&Hdr, &file).unwrap();
+        assert_eq!((image.width(), image.height()), (3, 2));
+        for (pixel, color) in image.pixels().iter().zip(colors) {
+            // Eight bits of mantissa for the brightest channel.
+            let tolerance = color.max() as f32 / 128.0 + 1e-6;
+            for c in 0..3 {
+                assert!((pixel[c] - color[c] as f32).abs() <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn hdr_run_length_encoding() {
+        let mut file = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+        file.extend_from_slice(&[2, 2, 0, 8]);
+        // Red as one run, green as eight literals, then blue and exponent as
+        // runs.
+        file.extend_from_slice(&[128 + 8, 128]);
+        file.extend_from_slice(&[8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        file.extend_from_slice(&[128 + 8, 0, 128 + 8, 136]);
+
+        let image = decode(&Hdr, &file).unwrap();
+        assert_eq!((image.width(), image.height()), (8, 1));
+        for x in 0..8 {
+            let g = 16.0 * x as f32 + 0.5;
+            assert_close(image.pixel(x, 0), [128.5, g, 0.5, 1.0]);
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_oversized_images() {
+        let resolutions = [
+            "-Y 0 +X 4",
+            "-Y 4 +X 0",
+            "-Y 100000 +X 100000",
+            "-Y 1 +X 100000",
+            "-Y 20000 +X 20000",
+            "-Y 4294967296 +X 4294967296",
+        ];
+        for resolution in resolutions {
+            let file = format!("#?RADIANCE\n\n{}\n", resolution);
+            assert!(matches!(
+                decode(&Hdr, file.as_bytes()),
+                Err(DecodeError::Size(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_png() {
+        let file = encode_png(
+            1,
+            MAX_DIMENSION as u32 + 1,
+            png::ColorType::Grayscale,
+            png::BitDepth::Eight,
+            &vec![0; MAX_DIMENSION + 1],
+            |_| {},
+        );
+        assert!(matches!(decode(&Png, &file), Err(DecodeError::Size(..))));
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        // Within the limits, but nowhere near that much data.
+        let file = b"#?RADIANCE\n\n-Y 8192 +X 16384\n\x80\x80\x80\x81";
+        assert!(matches!(decode(&Hdr, file), Err(DecodeError::Io(_))));
+
+        let file = b"#?RADIANCE\n\n-Y 2 +X 2\n\x80\x80\x80\x81";
+        assert!(matches!(decode(&Hdr, file), Err(DecodeError::Io(_))));
+
+        let file = encode_png(
+            2,
+            2,
+            png::ColorType::Rgb,
+            png::BitDepth::Eight,
+            &[0; 12],
+            |_| {},
+        );
+        assert!(decode(&Png, &file[..file.len() - 20]).is_err());
+    }
+
+    #[test]
+    fn picks_decoder_by_signature() {
+        let file = encode_png(
+            1,
+            1,
+            png::ColorType::Grayscale,
+            png::BitDepth::Eight,
+            &[0],
+            |_| {},
+        );
+        assert!(decoder_for_signature(&file).is_some());
+        assert!(decoder_for_signature(&[0xff, 0xd8, 0xff]).is_some());
+        assert!(decoder_for_signature(b"#?RADIANCE").is_some());
+        assert!(decoder_for_signature(b"P6\n").is_none());
+    }
+}
@@ -0,0 +1,488 @@
+use crate::render::Framebuffer;
+use crate::vec::Color;
+use std::io::{self, Write};
+
+/// Encodes a finished framebuffer into some image file format.
+pub trait ImageWriter {
+    fn write_image(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Clamps linear radiance to `0.0..=1.0` and applies the sRGB transfer
+/// curve, giving components ready to be quantized. Run the framebuffer
+/// through [`crate::tonemap`] first to keep highlights from clipping.
+pub fn encode_color(pixel_color: Color) -> Color {
+    let f = |x: f64| {
+        let x = x.clamp(0.0, 1.0);
+        if x <= 0.0031308 {
+            12.92 * x
+        } else {
+            1.055 * x.powf(1.0 / 2.4) - 0.055
+        }
+    };
+
+    pixel_color.map(f)
+}
+
+/// The inverse of [`encode_color`] over `0.0..=1.0`: values that should
+/// reach the file unchanged, like data rather than radiance, come back out
+/// of the 8 and 16 bit writers as they went in.
+pub fn decode_color(encoded: Color) -> Color {
+    let f = |x: f64| {
+        let x = x.clamp(0.0, 1.0);
+        if x <= 0.04045 {
+            x / 12.92
+        } else {
+            ((x + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    encoded.map(f)
+}
+
+fn quantize(x: f64, max: u32) -> u32 {
+    ((max + 1) as f64 * x).min(max as f64) as u32
+}
+
+fn encoded_pixels(framebuffer: &Framebuffer) -> impl Iterator<Item = Color> + '_ {
+    (0..framebuffer.height()).flat_map(move |y| {
+        (0..framebuffer.width()).map(move |x| encode_color(framebuffer.pixel(x, y)))
+    })
+}
+
+/// Netpbm pixmap, either as ASCII (`P3`) or binary (`P6`) with 8 bits per
+/// channel.
+pub struct Ppm {
+    pub binary: bool,
+}
+
+impl ImageWriter for Ppm {
+    fn write_image(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> io::Result<()> {
+        let magic = if self.binary { "P6" } else { "P3" };
+        writeln!(
+            writer,
+            "{}\n{} {}\n255",
+            magic,
+            framebuffer.width(),
+            framebuffer.height()
+        )?;
+
+        for color in encoded_pixels(framebuffer) {
+            let [r, g, b] = [color.x, color.y, color.z].map(|x| quantize(x, 255));
+            if self.binary {
+                writer.write_all(&[r as u8, g as u8, b as u8])?;
+            } else {
+                writeln!(writer, "{} {} {}", r, g, b)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// RGB PNG with 8 or 16 bits per channel, tagged as sRGB.
+pub struct Png {
+    pub sixteen_bit: bool,
+}
+
+impl ImageWriter for Png {
+    fn write_image(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, framebuffer.width(), framebuffer.height());
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(if self.sixteen_bit {
+            png::BitDepth::Sixteen
+        } else {
+            png::BitDepth::Eight
+        });
+        // Unlike its replacement in newer `png` releases, this also emits the
+        // matching gAMA and cHRM chunks.
+        #[allow(deprecated)]
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+
+        let mut data = Vec::new();
+        for color in encoded_pixels(framebuffer) {
+            for x in [color.x, color.y, color.z] {
+                if self.sixteen_bit {
+                    data.extend_from_slice(&(quantize(x, 65535) as u16).to_be_bytes());
+                } else {
+                    data.push(quantize(x, 255) as u8);
+                }
+            }
+        }
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Portable float map: three little-endian `f32`s per pixel, bottom row
+/// first.
+pub struct Pfm;
+
+impl ImageWriter for Pfm {
+    fn write_image(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> io::Result<()> {
+        // A negative scale marks the data as little-endian.
+        writeln!(
+            writer,
+            "PF\n{} {}\n-1.0",
+            framebuffer.width(),
+            framebuffer.height()
+        )?;
+
+        let mut data = Vec::with_capacity(framebuffer.pixels().len() * 12);
+        for y in (0..framebuffer.height()).rev() {
+            for x in 0..framebuffer.width() {
+                for c in framebuffer.pixel(x, y).iter() {
+                    data.extend_from_slice(&(*c as f32).to_le_bytes());
+                }
+            }
+        }
+        writer.write_all(&data)
+    }
+}
+
+/// Radiance picture (`.hdr`) with uncompressed RGBE scanlines.
+pub struct Rgbe;
+
+impl Rgbe {
+    /// Shares one exponent between the three channels, keeping 8 bits of
+    /// mantissa for the brightest one. Infinities and anything too bright
+    /// for the 8 bit exponent become the largest encodable value, NaNs
+    /// become black.
+    fn encode(color: Color) -> [u8; 4] {
+        const LARGEST: f64 = 255.0 / 256.0 * (1u128 << 127) as f64;
+        let color = color.map(|x| {
+            if x.is_nan() {
+                0.0
+            } else {
+                x.clamp(0.0, LARGEST)
+            }
+        });
+        let v = color.max();
+        if v < 1e-32 {
+            return [0; 4];
+        }
+
+        let mut e = v.log2().floor() as i32 + 1;
+        if v / (e as f64).exp2() >= 1.0 {
+            e += 1;
+        }
+        let scale = 256.0 / (e as f64).exp2();
+        let f = |x: f64| (x.max(0.0) * scale) as u8;
+
+        [f(color.x), f(color.y), f(color.z), (e + 128) as u8]
+    }
+}
+
+impl ImageWriter for Rgbe {
+    fn write_image(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> io::Result<()> {
+        write!(
+            writer,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            framebuffer.height(),
+            framebuffer.width()
+        )?;
+
+        let mut data = Vec::with_capacity(framebuffer.pixels().len() * 4);
+        for pixel in framebuffer.pixels() {
+            data.extend_from_slice(&Self::encode(*pixel));
+        }
+        writer.write_all(&data)
+    }
+}
+
+/// Single-part scanline OpenEXR with uncompressed 32-bit float `R`, `G` and
+/// `B` channels.
+pub struct Exr;
+
+impl Exr {
+    fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    }
+
+    fn header(width: u32, height: u32) -> Vec<u8> {
+        const FLOAT: i32 = 2;
+
+        let mut channels = Vec::new();
+        // Channels are listed, and stored, in alphabetical order.
+        for name in ["B", "G", "R"] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&FLOAT.to_le_bytes());
+            // pLinear and three reserved bytes.
+            channels.extend_from_slice(&[0; 4]);
+            // x and y sampling.
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&1i32.to_le_bytes());
+        }
+        channels.push(0);
+
+        let mut window = Vec::new();
+        for v in [0, 0, width as i32 - 1, height as i32 - 1] {
+            window.extend_from_slice(&v.to_le_bytes());
+        }
+
+        let mut header = Vec::new();
+        // Magic number, then version 2 with no flags: single part, scanlines.
+        header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        Self::attribute(&mut header, "channels", "chlist", &channels);
+        Self::attribute(&mut header, "compression", "compression", &[0]);
+        Self::attribute(&mut header, "dataWindow", "box2i", &window);
+        Self::attribute(&mut header, "displayWindow", "box2i", &window);
+        // Increasing y.
+        Self::attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        Self::attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        Self::attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        Self::attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        header.push(0);
+        header
+    }
+}
+
+impl ImageWriter for Exr {
+    fn write_image(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> io::Result<()> {
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        let header = Self::header(width, height);
+
+        // Without compression every chunk holds a single scanline: its y
+        // coordinate, the data size, then each channel's row of floats.
+        let line_size = width as u64 * 3 * 4;
+        let chunk_size = 8 + line_size;
+        let first_chunk = header.len() as u64 + 8 * height as u64;
+
+        let mut data = header;
+        for y in 0..height as u64 {
+            data.extend_from_slice(&(first_chunk + y * chunk_size).to_le_bytes());
+        }
+        for y in 0..height {
+            data.extend_from_slice(&(y as i32).to_le_bytes());
+            data.extend_from_slice(&(line_size as i32).to_le_bytes());
+            for channel in [2, 1, 0] {
+                for x in 0..width {
+                    let c = framebuffer.pixel(x, y)[channel] as f32;
+                    data.extend_from_slice(&c.to_le_bytes());
+                }
+            }
+        }
+        writer.write_all(&data)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Png16,
+    Ppm,
+    PpmAscii,
+    Pfm,
+    Hdr,
+    Exr,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] =
+        &["png", "png16", "ppm", "ppm-ascii", "pfm", "hdr", "exr"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "png16" => Some(Format::Png16),
+            "ppm" => Some(Format::Ppm),
+            "ppm-ascii" => Some(Format::PpmAscii),
+            "pfm" => Some(Format::Pfm),
+            "hdr" => Some(Format::Hdr),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+
+    /// The format to use for a file with this extension.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "ppm" | "pnm" => Some(Format::Ppm),
+            "pfm" => Some(Format::Pfm),
+            "hdr" | "pic" => Some(Format::Hdr),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+
+    /// Whether the format stores linear floating point radiance, in which
+    /// case the framebuffer should be written without tone mapping.
+    pub fn is_hdr(self) -> bool {
+        matches!(self, Format::Pfm | Format::Hdr | Format::Exr)
+    }
+
+    pub fn writer(self) -> Box<dyn ImageWriter> {
+        match self {
+            Format::Png => Box::new(Png { sixteen_bit: false }),
+            Format::Png16 => Box::new(Png { sixteen_bit: true }),
+            Format::Ppm => Box::new(Ppm { binary: true }),
+            Format::PpmAscii => Box::new(Ppm { binary: false }),
+            Format::Pfm => Box::new(Pfm),
+            Format::Hdr => Box::new(Rgbe),
+            Format::Exr => Box::new(Exr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2x2 image: black, white, mid gray and red, top row first.
+    fn framebuffer() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(2, 2);
+        framebuffer.pixels_mut().copy_from_slice(&[
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+            Color::new(0.5, 0.5, 0.5),
+            Color::new(4.0, 0.0, 0.0),
+        ]);
+        framebuffer
+    }
+
+    fn write(writer: &dyn ImageWriter, framebuffer: &Framebuffer) -> Vec<u8> {
+        let mut data = Vec::new();
+        writer.write_image(framebuffer, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn encode_color_applies_srgb_curve() {
+        let encoded = encode_color(Color::new(0.0, 0.5, 2.0));
+        assert_eq!(encoded.x, 0.0);
+        assert!((encoded.y - 0.7354).abs() < 1e-4);
+        assert!((encoded.z - 1.0).abs() < 1e-12);
+        let decoded = decode_color(encoded);
+        assert!((decoded.y - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn binary_ppm_layout() {
+        let data = write(&Ppm { binary: true }, &framebuffer());
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(
+            &data[header.len()..],
+            &[0, 0, 0, 255, 255, 255, 188, 188, 188, 255, 0, 0]
+        );
+    }
+
+    #[test]
+    fn ascii_ppm_layout() {
+        let data = write(&Ppm { binary: false }, &framebuffer());
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "P3\n2 2\n255\n0 0 0\n255 255 255\n188 188 188\n255 0 0\n"
+        );
+    }
+
+    #[test]
+    fn png_round_trips() {
+        for sixteen_bit in [false, true] {
+            let data = write(&Png { sixteen_bit }, &framebuffer());
+            let mut reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut pixels).unwrap();
+            assert_eq!((info.width, info.height), (2, 2));
+            assert_eq!(info.color_type, png::ColorType::Rgb);
+            assert!(reader.info().srgb.is_some());
+            if sixteen_bit {
+                assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+                assert_eq!(&pixels[6..12], &[0xff; 6]);
+            } else {
+                assert_eq!(pixels, [0, 0, 0, 255, 255, 255, 188, 188, 188, 255, 0, 0]);
+            }
+        }
+    }
+
+    fn floats(data: &[u8]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn pfm_layout() {
+        let data = write(&Pfm, &framebuffer());
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&data[..header.len()], header);
+        // Bottom row first.
+        assert_eq!(
+            floats(&data[header.len()..]),
+            [0.5, 0.5, 0.5, 4.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn rgbe_layout() {
+        let data = write(&Rgbe, &framebuffer());
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 2\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(
+            &data[header.len()..],
+            &[0, 0, 0, 0, 128, 128, 128, 129, 128, 128, 128, 128, 128, 0, 0, 131]
+        );
+    }
+
+    #[test]
+    fn rgbe_clamps_out_of_range_values() {
+        assert_eq!(
+            Rgbe::encode(Color::new(f64::INFINITY, 0.0, 0.0)),
+            [255, 0, 0, 255]
+        );
+        assert_eq!(
+            Rgbe::encode(Color::new(1e300, 1e300, 0.0)),
+            [255, 255, 0, 255]
+        );
+        assert_eq!(Rgbe::encode(Color::new(f64::NAN, -1.0, 0.0)), [0; 4]);
+        assert_eq!(
+            Rgbe::encode(Color::new(f64::NAN, 1.0, 0.0)),
+            [0, 128, 0, 129]
+        );
+        assert_eq!(Rgbe::encode(Color::new(1e-40, 0.0, 0.0)), [0; 4]);
+    }
+
+    #[test]
+    fn exr_layout() {
+        let data = write(&Exr, &framebuffer());
+        assert_eq!(&data[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        let header = Exr::header(2, 2);
+        assert_eq!(&data[..header.len()], header.as_slice());
+        assert_eq!(header.last(), Some(&0));
+
+        // Two offsets, then two chunks of y, size and 2 pixels by 3 floats.
+        let chunk_size = 8 + 2 * 3 * 4;
+        assert_eq!(data.len(), header.len() + 2 * 8 + 2 * chunk_size);
+        for y in 0..2 {
+            let at = header.len() + 8 * y;
+            let offset = u64::from_le_bytes(data[at..at + 8].try_into().unwrap()) as usize;
+            assert_eq!(offset, header.len() + 16 + y * chunk_size);
+            let chunk = &data[offset..offset + chunk_size];
+            assert_eq!(i32::from_le_bytes(chunk[..4].try_into().unwrap()), y as i32);
+            assert_eq!(i32::from_le_bytes(chunk[4..8].try_into().unwrap()), 24);
+        }
+
+        // The bottom row, channels in B, G, R order.
+        let last = data.len() - chunk_size;
+        assert_eq!(floats(&data[last + 8..]), [0.5, 0.0, 0.5, 0.0, 0.5, 4.0]);
+    }
+}
@@ -0,0 +1,290 @@
+//! Integrators turn a camera ray into the color its pixel sample gets. The
+//! path tracer computes the actual image; the others draw one property of
+//! the first hit, to check geometry and acceleration structures quickly.
+
+use crate::camera::CameraSettings;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::vec::{self, Color};
+
+/// How far rays start from the surface they leave, to avoid hitting it
+/// again through rounding errors.
+const EPSILON: f64 = 0.001;
+
+pub trait Integrator: Send + Sync {
+    /// The color seen along the camera ray `r`, with any random decisions
+    /// taken from `sampler`.
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color;
+}
+
+/// Follows paths bounce by bounce, tracking how much of the light found
+/// further on still reaches the camera. Every bounce off a non-delta
+/// material also samples one of the scene's lights, and both that sample
+/// and the light the BSDF sample runs into are weighted with the power
+/// heuristic.
+///
+/// After `roulette_depth` bounces, paths are ended at random with a
+/// probability that grows as their throughput drops, and the survivors
+/// are scaled up to make up for it. That keeps the estimate unbiased;
+/// `max_depth` is only a safety net for paths stuck between mirrors.
+pub struct PathTracer {
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+}
+
+impl PathTracer {
+    pub fn new(max_depth: u32, roulette_depth: u32) -> Self {
+        Self {
+            max_depth,
+            roulette_depth,
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let mut color = Color::zeros();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = r.clone();
+        // The density with which the last bounce picked `ray`, or `None` if
+        // no light sample could have: camera rays and delta lobes.
+        let mut bsdf_pdf = None;
+
+        for bounce in 1..=self.max_depth {
+            let rec = match scene.world.hit(&ray, EPSILON..f64::INFINITY, sampler) {
+                Some(rec) => rec,
+                None => {
+                    color += throughput.component_mul(&scene.background.color(&ray));
+                    break;
+                }
+            };
+
+            let mut emitted = rec.material.emitted(rec.uv, &rec.p);
+            if let Some(pdf) = bsdf_pdf {
+                if emitted != Color::zeros() {
+                    emitted *= power_heuristic(pdf, scene.lights.pdf_towards(&ray, sampler));
+                }
+            }
+            color += throughput.component_mul(&emitted);
+
+            let sample = match rec.material.sample(&ray, &rec, sampler) {
+                Some(sample) => sample,
+                None => break,
+            };
+            if sample.pdf.is_some() {
+                color += throughput.component_mul(&sample_light(&ray, &rec, scene, sampler));
+            }
+            throughput.component_mul_assign(&sample.weight);
+
+            if bounce >= self.roulette_depth {
+                let survival = throughput.max().min(0.95);
+                if sampler.get_1d() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+            ray = sample.ray;
+            bsdf_pdf = sample.pdf;
+        }
+        color
+    }
+}
+
+/// Light reaching `rec` from a direction picked by the scene's lights.
+fn sample_light(r: &Ray, rec: &HitRecord, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let direction = match scene.lights.sample_towards(&rec.p, sampler) {
+        Some(direction) => direction,
+        None => return black,
+    };
+    let shadow_ray = Ray::new(rec.p, direction, r.time());
+    let light_pdf = scene.lights.pdf_towards(&shadow_ray, sampler);
+    let f = rec.material.eval(r, rec, &direction);
+    if light_pdf <= 0.0 || f == black {
+        return black;
+    }
+
+    // Whatever the ray hits first is what lights the point, which is
+    // the sampled light unless something is in the way.
+    match scene
+        .world
+        .hit(&shadow_ray, EPSILON..f64::INFINITY, sampler)
+    {
+        Some(light) => {
+            let weight = power_heuristic(light_pdf, rec.material.pdf(r, rec, &direction));
+            f.component_mul(&light.material.emitted(light.uv, &light.p)) * weight / light_pdf
+        }
+        None => black,
+    }
+}
+
+/// The weight of a sample drawn with density `pdf` against another strategy
+/// that would have drawn it with density `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+/// Outward surface normals, mapped from `-1..1` to `0..1` per axis.
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        match scene.world.hit(r, EPSILON..f64::INFINITY, sampler) {
+            Some(rec) => {
+                let outward = if rec.front_face {
+                    rec.normal
+                } else {
+                    -rec.normal
+                };
+                0.5 * (outward + Color::new(1.0, 1.0, 1.0))
+            }
+            None => Color::zeros(),
+        }
+    }
+}
+
+/// Texture coordinates as red and green.
+pub struct Uv;
+
+impl Integrator for Uv {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        match scene.world.hit(r, EPSILON..f64::INFINITY, sampler) {
+            Some(rec) => Color::new(rec.uv.0, rec.uv.1, 0.0),
+            None => Color::zeros(),
+        }
+    }
+}
+
+/// Distance to the first hit, black up close to white at `far` and beyond.
+/// Misses are white too.
+pub struct Depth {
+    pub far: f64,
+}
+
+impl Integrator for Depth {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let distance = match scene.world.hit(r, EPSILON..f64::INFINITY, sampler) {
+            Some(rec) => rec.t * r.direction().norm(),
+            None => f64::INFINITY,
+        };
+        Color::from_element((distance / self.far).min(1.0))
+    }
+}
+
+/// The fraction of a cosine-weighted hemisphere around the first hit that
+/// is open for `radius` or further. One occlusion ray per call, so noise
+/// goes away with more samples per pixel.
+pub struct AmbientOcclusion {
+    pub radius: f64,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let rec = match scene.world.hit(r, EPSILON..f64::INFINITY, sampler) {
+            Some(rec) => rec,
+            None => return Color::new(1.0, 1.0, 1.0),
+        };
+        let direction = vec::cosine_sample(&rec.normal, sampler.get_2d());
+        let probe = Ray::new(rec.p, direction, r.time());
+        if scene
+            .world
+            .hit(&probe, EPSILON..self.radius, sampler)
+            .is_some()
+        {
+            Color::zeros()
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+/// How many bounding boxes and primitives the camera ray was tested
+/// against, from blue for none through green to red at `max_tests` and
+/// beyond.
+pub struct BvhCost {
+    pub max_tests: usize,
+}
+
+impl Integrator for BvhCost {
+    fn radiance(&self, r: &Ray, scene: &Scene, sampler: &mut dyn Sampler) -> Color {
+        let mut tests = 0;
+        scene
+            .world
+            .hit_counting(r, EPSILON..f64::INFINITY, &mut tests, sampler);
+        heat((tests as f64 / self.max_tests as f64).min(1.0))
+    }
+}
+
+/// Blue, cyan, green, yellow and red at `t` = 0, 0.25, 0.5, 0.75 and 1.
+fn heat(t: f64) -> Color {
+    const STOPS: [[f64; 3]; 5] = [
+        [0.0, 0.0, 1.0],
+        [0.0, 1.0, 1.0],
+        [0.0, 1.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [1.0, 0.0, 0.0],
+    ];
+    let x = t.clamp(0.0, 1.0) * 4.0;
+    let i = (x as usize).min(3);
+    let (a, b) = (Color::from(STOPS[i]), Color::from(STOPS[i + 1]));
+    a + (b - a) * (x - i as f64)
+}
+
+/// The integrators the command line can pick by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegratorKind {
+    Path,
+    Normals,
+    Uv,
+    Depth,
+    AmbientOcclusion,
+    BvhCost,
+}
+
+impl IntegratorKind {
+    pub const NAMES: &'static [&'static str] =
+        &["path", "normals", "uv", "depth", "ao", "bvh-cost"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "path" => Some(IntegratorKind::Path),
+            "normals" => Some(IntegratorKind::Normals),
+            "uv" => Some(IntegratorKind::Uv),
+            "depth" => Some(IntegratorKind::Depth),
+            "ao" => Some(IntegratorKind::AmbientOcclusion),
+            "bvh-cost" => Some(IntegratorKind::BvhCost),
+            _ => None,
+        }
+    }
+
+    /// Builds the integrator. Distances scale with how far the camera is
+    /// from what it looks at: depth turns white at twice that distance, and
+    /// occlusion is searched up to a tenth of it.
+    pub fn build(
+        self,
+        camera: &CameraSettings,
+        max_depth: u32,
+        roulette_depth: u32,
+    ) -> Box<dyn Integrator> {
+        let distance = (camera.lookat - camera.lookfrom).norm();
+        match self {
+            IntegratorKind::Path => Box::new(PathTracer::new(max_depth, roulette_depth)),
+            IntegratorKind::Normals => Box::new(Normals),
+            IntegratorKind::Uv => Box::new(Uv),
+            IntegratorKind::Depth => Box::new(Depth {
+                far: 2.0 * distance,
+            }),
+            IntegratorKind::AmbientOcclusion => Box::new(AmbientOcclusion {
+                radius: 0.1 * distance,
+            }),
+            IntegratorKind::BvhCost => Box::new(BvhCost { max_tests: 100 }),
+        }
+    }
+}
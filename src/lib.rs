@@ -0,0 +1,39 @@
+//! A small path tracer following the "Ray Tracing in One Weekend" series.
+//!
+//! Scenes are built from [`hittable::Hittable`] objects carrying
+//! [`material::Material`]s, collected into a [`scene::Scene`] together with a
+//! [`camera::Camera`], and turned into an image with [`render::render`] and
+//! an [`integrator::Integrator`].
+
+pub mod aabb;
+pub mod box_shape;
+pub mod bvh;
+pub mod camera;
+pub mod circle;
+pub mod constant_medium;
+pub mod filter;
+pub mod hittable;
+pub mod hittable_list;
+pub mod image_reader;
+pub mod image_writer;
+pub mod integrator;
+pub mod material;
+pub mod moving_sphere;
+pub mod obj;
+pub mod perlin;
+pub mod ray;
+pub mod rect;
+pub mod render;
+pub mod sampler;
+pub mod scene;
+pub mod scene_file;
+pub mod scenes;
+pub mod texture;
+pub mod tonemap;
+pub mod transform;
+pub mod triangle;
+pub mod util;
+pub mod vec;
+
+pub use render::{render, Framebuffer, Progress, RenderOutput, RenderSettings};
+pub use scene::Scene;
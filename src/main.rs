@@ -1,165 +1,166 @@
-mod aabb;
-mod bvh;
-mod camera;
-mod circle;
-mod hittable;
-mod hittable_list;
-mod material;
-mod moving_sphere;
-mod ray;
-mod texture;
-mod util;
-mod vec;
-
-use circle::Sphere;
-use hittable::Hittable;
-use hittable_list::HittableList;
-use material::{Dielectric, Lambertian, Material, Metal};
-use moving_sphere::MovingSphere;
-use ray::Ray;
-use std::{fs::File, rc::Rc};
-use texture::{Image, SolidColor};
-use vec::{Color, Point, Vec};
-
-fn ray_color(r: &Ray, world: &dyn Hittable, depth: u32) -> Color {
-    if depth <= 0 {
-        Color::new(0.0, 0.0, 0.0)
-    } else if let Some(rec) = world.hit(r, 0.001..f64::INFINITY) {
-        if let Some((scattered, attenuation)) = rec.material.scatter(r, &rec) {
-            attenuation.component_mul(&ray_color(&scattered, world, depth - 1))
-        } else {
-            Color::new(0.0, 0.0, 0.0)
-        }
+mod cli;
+
+use cli::{Command, SceneSource};
+use rayt::image_writer::{self, Format};
+use rayt::render::{self, Framebuffer, Progress, RenderOutput, RenderSettings, Snapshots};
+use rayt::{scene_file, scenes, tonemap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set on the first SIGINT of a progressive render.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+fn write_image(framebuffer: &Framebuffer, options: &cli::Options) -> std::io::Result<()> {
+    let mut writer: Box<dyn Write> = match &options.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    let image_writer = options.format.writer();
+    if options.format.is_hdr() {
+        image_writer.write_image(framebuffer, &mut writer)?;
     } else {
-        let t = 0.5 * (r.unit_direction().y + 1.0);
-        Color::new(1.0, 1.0, 1.0) * (1.0 - t) + t * Color::new(0.5, 0.7, 1.0)
+        let mapped = tonemap::tone_map(framebuffer, options.tone_map, options.exposure);
+        image_writer.write_image(&mapped, &mut writer)?;
     }
+    writer.flush()
 }
 
-fn random_scene() -> HittableList {
-    let mut world = HittableList::new();
-
-    let ground_material = Rc::new(Lambertian::from_texture(Rc::new(texture::Checker::new(
-        Rc::new(SolidColor::new(0.2, 0.3, 0.1)),
-        Rc::new(SolidColor::new(0.9, 0.9, 0.9)),
-    ))));
-    world.add(Rc::new(Sphere::new(
-        Point::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = util::random_f64();
-            let center = Vec::new(
-                a as f64 + 0.9 * util::random_f64(),
-                0.2,
-                b as f64 + 0.9 * util::random_f64(),
-            );
-
-            if (center - Point::new(4.0, 0.2, 0.0)).norm() > 0.9 {
-                let sphere_material: Rc<dyn Material> = {
-                    if choose_mat < 0.8 {
-                        // diffuse
-                        let albedo = vec::random().component_mul(&vec::random());
-                        Rc::new(Lambertian::new(albedo))
-                    } else if choose_mat < 0.95 {
-                        // metal
-                        let albedo = vec::random_range(0.5..1.0);
-                        let fuzz = util::random_f64_range(0.0..0.5);
-                        Rc::new(Metal::new(albedo, fuzz))
-                    } else {
-                        // glass
-                        Rc::new(Dielectric::new(1.5))
-                    }
-                };
-
-                if choose_mat < 0.8 {
-                    let center2 = center + Vec::new(0.0, util::random_f64_range(0.0..0.5), 0.0);
-                    world.add(Rc::new(MovingSphere::new(
-                        center,
-                        center2,
-                        0.0..1.0,
-                        0.2,
-                        sphere_material,
-                    )))
-                } else {
-                    world.add(Rc::new(Sphere::new(center, 0.2, sphere_material)))
-                }
-            }
+/// Writes the sample-count map in the format its extension names, PNG
+/// otherwise. Gray levels stay proportional to the sample counts: 8 and 16
+/// bit formats get the map with their sRGB curve undone in advance.
+fn write_sample_map(sample_map: &Framebuffer, path: &Path) -> std::io::Result<()> {
+    let format = path
+        .extension()
+        .and_then(|ext| Format::from_extension(&ext.to_string_lossy()))
+        .unwrap_or(Format::Png);
+    let mut sample_map = sample_map.clone();
+    if !format.is_hdr() {
+        for pixel in sample_map.pixels_mut() {
+            *pixel = image_writer::decode_color(*pixel);
         }
     }
-
-    let material = Rc::new(Dielectric::new(1.5));
-    world.add(Rc::new(Sphere::new(
-        Point::new(0.0, 1.0, 0.0),
-        1.0,
-        material,
-    )));
-
-    let material_earth = Rc::new(Lambertian::from_texture(Rc::new(
-        Image::from_png_file(&File::open("res/2k_earth_daymap.png").unwrap()).unwrap(),
-    )));
-    world.add(Rc::new(Sphere::new(
-        Point::new(-4.0, 1.0, 0.0),
-        1.0,
-        material_earth.clone(),
-    )));
-
-    let material = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Rc::new(Sphere::new(
-        Point::new(4.0, 1.0, 0.0),
-        1.0,
-        material_earth,
-    )));
-
-    /* let mut bv = HittableList::new();
-    bv.add(Rc::new(bvh::BvhNode::new(&world, 0.0..1.0)));
-    bv */
-    world
+    let mut writer = BufWriter::new(File::create(path)?);
+    format.writer().write_image(&sample_map, &mut writer)?;
+    writer.flush()
 }
 
-fn main() -> std::io::Result<()> {
-    const ASPECT_RATIO: f64 = 3.0 / 2.0;
-    const IMAGE_WIDTH: u32 = 400;
-    const IMAGE_HEIGHT: u32 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as u32;
-    const SAMPLES_PER_PIXEL: u32 = 100;
-    const MAX_DEPTH: u32 = 50;
-
-    let world = random_scene();
+/// Keeps a count of what's left of the render on one line of stderr.
+fn show_progress(progress: Progress) {
+    match progress {
+        Progress::Tiles { remaining } => eprint!("\rTiles remaining: {:04}", remaining),
+        Progress::Passes { remaining } => eprint!("\rPasses remaining: {:04}", remaining),
+    }
+}
 
-    let lookfrom = Point::new(13.0, 2.0, 3.0);
-    let lookat = Point::new(0.0, 0.0, 0.0);
-    let vup = Vec::new(0.0, 1.0, 0.0);
+/// Writes the image and, if asked for, the sample-count map.
+fn write_output(output: &RenderOutput, options: &cli::Options) -> Result<(), String> {
+    write_image(&output.image, options).map_err(|err| err.to_string())?;
+    if let Some(path) = &options.sample_map {
+        write_sample_map(&output.sample_map, path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    Ok(())
+}
 
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
+fn main() -> ExitCode {
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::HELP);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\nRun `rayt --help` for usage.", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut scene = match &options.scene {
+        SceneSource::Builtin(name) => match scenes::builtin(name, options.seed) {
+            Ok(scene) => scene,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+        SceneSource::File(path) => match scene_file::load(path, options.seed) {
+            Ok(scene) => scene,
+            Err(err @ scene_file::SceneError::Syntax(..)) => {
+                eprintln!("error: {}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    if let Some(lookfrom) = options.lookfrom {
+        scene.camera.lookfrom = lookfrom;
+    }
+    if let Some(lookat) = options.lookat {
+        scene.camera.lookat = lookat;
+    }
 
-    let cam = camera::Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        20.0,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-    );
+    let mut settings = RenderSettings {
+        image_width: options.width,
+        image_height: options.image_height(),
+        samples_per_pixel: options.samples_per_pixel,
+        error_threshold: options.error_threshold,
+        sampler: options.sampler,
+        filter: options.filter,
+        filter_radius: options.filter_radius,
+        seed: options.seed,
+        ..Default::default()
+    };
+    if let Some(threads) = options.threads {
+        settings.threads = threads;
+    }
 
-    println!("P3\n{} {}\n255\n", IMAGE_WIDTH, IMAGE_HEIGHT);
-    for j in (0..IMAGE_HEIGHT).rev() {
-        eprint!("\rScanlines remaining: {:03}", j);
-        for i in 0..IMAGE_WIDTH {
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..SAMPLES_PER_PIXEL {
-                let u = (i as f64 + util::random_f64()) / (IMAGE_WIDTH as f64 - 1.0);
-                let v = (j as f64 + util::random_f64()) / (IMAGE_HEIGHT as f64 - 1.0);
-                pixel_color += ray_color(&cam.get_ray(u, v, 0.0..1.0), &world, MAX_DEPTH);
+    let (max_depth, roulette_depth) = (options.max_depth, options.roulette_depth);
+    let integrator = options
+        .integrator
+        .build(&scene.camera, max_depth, roulette_depth);
+    let output = if options.progressive {
+        // A first interrupt ends the render after the tiles in progress and
+        // saves it, a second one quits right away.
+        let handler = ctrlc::set_handler(|| {
+            if STOP.swap(true, Ordering::Relaxed) {
+                std::process::exit(130);
             }
-            vec::write_color(&mut std::io::stdout(), pixel_color, SAMPLES_PER_PIXEL)?;
+            eprintln!("\nStopping, interrupt again to quit without saving");
+        });
+        if let Err(err) = handler {
+            eprintln!("warning: can't catch interrupts: {}", err);
         }
-    }
-    eprintln!();
 
-    Ok(())
+        let snapshots = Snapshots {
+            passes: options.snapshot_passes,
+            interval: options.snapshot_interval,
+        };
+        render::render_progressive(
+            &scene,
+            &*integrator,
+            &settings,
+            &snapshots,
+            &STOP,
+            |output| {
+                if let Err(err) = write_output(output, &options) {
+                    eprintln!("\nerror: {}", err);
+                }
+            },
+            show_progress,
+        )
+    } else {
+        render::render(&scene, &*integrator, &settings, show_progress)
+    };
+    eprintln!();
+    if let Err(err) = write_output(&output, &options) {
+        eprintln!("error: {}", err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
 }
@@ -1,27 +1,74 @@
-use std::rc::Rc;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
 use crate::hittable::HitRecord;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::texture::{SolidColor, Texture};
-use crate::util;
-use crate::vec::{self, Color, Vec};
+use crate::vec::{self, Color, Point, Vec};
 
-pub trait Material {
-    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<(Ray, Vec)>;
+/// A direction picked by [`Material::sample`].
+pub struct BsdfSample {
+    pub ray: Ray,
+    /// What the path's throughput gets multiplied by: the BSDF times the
+    /// cosine term, divided by `pdf`.
+    pub weight: Color,
+    /// The density per unit solid angle with which the direction was
+    /// picked, or `None` for a delta lobe such as a mirror, which light
+    /// sampling can't hit.
+    pub pdf: Option<f64>,
+}
+
+pub trait Material: Send + Sync {
+    fn scatter(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Ray, Vec)>;
+
+    /// Light given off at the hit point, black for everything but lights.
+    fn emitted(&self, _uv: (f64, f64), _p: &Point) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Whether [`emitted`](Self::emitted) can be non-zero, so that objects
+    /// made of the material are worth sampling as lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Picks the direction the path continues in. By default that is
+    /// [`scatter`](Self::scatter), treated as a delta lobe.
+    fn sample(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+        self.scatter(r, rec, sampler)
+            .map(|(ray, attenuation)| BsdfSample {
+                ray,
+                weight: attenuation,
+                pdf: None,
+            })
+    }
+
+    /// The BSDF times the cosine term for light arriving from `direction`,
+    /// which need not be normalized. Black for delta lobes.
+    fn eval(&self, _r: &Ray, _rec: &HitRecord, _direction: &Vec) -> Color {
+        Color::zeros()
+    }
+
+    /// The density with which [`sample`](Self::sample) picks `direction`.
+    /// Zero for delta lobes.
+    fn pdf(&self, _r: &Ray, _rec: &HitRecord, _direction: &Vec) -> f64 {
+        0.0
+    }
 }
 
 pub struct Lambertian {
-    albedo: Rc<dyn Texture>,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
         Self {
-            albedo: Rc::new(SolidColor::from_color(albedo)),
+            albedo: Arc::new(SolidColor::from_color(albedo)),
         }
     }
 
-    pub fn from_texture(albedo: Rc<dyn Texture>) -> Self {
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
         Self {
             albedo,
         }
@@ -29,30 +76,49 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let scatter_direction = rec.normal + vec::random_unit_vector();
+    fn scatter(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Ray, Color)> {
         Some((
             Ray::new(
                 rec.p,
-                if vec::near_zero(scatter_direction) {
-                    rec.normal
-                } else {
-                    scatter_direction
-                },
+                vec::cosine_sample(&rec.normal, sampler.get_2d()),
                 r.time(),
             ),
-            self.albedo.value(rec.uv, &rec.p),
+            self.albedo.filtered_value(rec.uv, &rec.p, rec.footprint),
         ))
     }
+
+    /// `scatter` already picks cosine-distributed directions.
+    fn sample(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+        let (ray, weight) = self.scatter(r, rec, sampler)?;
+        let pdf = self.pdf(r, rec, ray.direction());
+        Some(BsdfSample {
+            ray,
+            weight,
+            pdf: Some(pdf),
+        })
+    }
+
+    fn eval(&self, _r: &Ray, rec: &HitRecord, direction: &Vec) -> Color {
+        let cosine = rec.normal.dot(&direction.normalize()).max(0.0);
+        self.albedo.filtered_value(rec.uv, &rec.p, rec.footprint) * cosine / PI
+    }
+
+    fn pdf(&self, _r: &Ray, rec: &HitRecord, direction: &Vec) -> f64 {
+        rec.normal.dot(&direction.normalize()).max(0.0) / PI
+    }
 }
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::from_color(albedo)), fuzz)
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self {
             albedo,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
@@ -61,16 +127,13 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Ray, Color)> {
         let reflected = vec::reflect(&r.direction().normalize(), &rec.normal);
+        let fuzz = vec::unit_sphere_sample(sampler.get_2d(), sampler.get_1d());
         if reflected.dot(&rec.normal) > 0.0 {
             Some((
-                Ray::new(
-                    rec.p,
-                    reflected + self.fuzz * vec::random_in_unit_sphere(),
-                    r.time(),
-                ),
-                self.albedo,
+                Ray::new(rec.p, reflected + self.fuzz * fuzz, r.time()),
+                self.albedo.filtered_value(rec.uv, &rec.p, rec.footprint),
             ))
         } else {
             None
@@ -95,7 +158,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Ray, Color)> {
         let refraction_ratio = if rec.front_face {
             1.0 / self.ir
         } else {
@@ -110,7 +173,7 @@ impl Material for Dielectric {
             Ray::new(
                 rec.p,
                 if refraction_ratio * sin_theta > 1.0
-                    || Dielectric::reflectance(cos_theta, refraction_ratio) > util::random_f64()
+                    || Dielectric::reflectance(cos_theta, refraction_ratio) > sampler.get_1d()
                 {
                     vec::reflect(&unit_direction, &rec.normal)
                 } else {
@@ -122,3 +185,82 @@ impl Material for Dielectric {
         ))
     }
 }
+
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self {
+            emit: Arc::new(SolidColor::from_color(emit)),
+        }
+    }
+
+    pub fn from_texture(emit: Arc<dyn Texture>) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _r: &Ray,
+        _rec: &HitRecord,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emitted(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.emit.value(uv, p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+/// Scatters uniformly in every direction. Used as the phase function of
+/// participating media.
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self {
+            albedo: Arc::new(SolidColor::from_color(albedo)),
+        }
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Ray, Color)> {
+        Some((
+            Ray::new(rec.p, vec::unit_vector_sample(sampler.get_2d()), r.time()),
+            self.albedo.filtered_value(rec.uv, &rec.p, rec.footprint),
+        ))
+    }
+
+    fn sample(&self, r: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<BsdfSample> {
+        let (ray, weight) = self.scatter(r, rec, sampler)?;
+        Some(BsdfSample {
+            ray,
+            weight,
+            pdf: Some(1.0 / (4.0 * PI)),
+        })
+    }
+
+    fn eval(&self, _r: &Ray, rec: &HitRecord, _direction: &Vec) -> Color {
+        self.albedo.filtered_value(rec.uv, &rec.p, rec.footprint) / (4.0 * PI)
+    }
+
+    fn pdf(&self, _r: &Ray, _rec: &HitRecord, _direction: &Vec) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
@@ -2,17 +2,18 @@ use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec::{Point, Vec};
 use std::f64::consts::PI;
 use std::ops::Range;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub struct MovingSphere {
     center0: Point,
     center1: Point,
     time_range: Range<f64>,
     radius: f64,
-    material: Rc<dyn Material>,
+    material: Arc<dyn Material>,
 }
 
 impl MovingSphere {
@@ -21,7 +22,7 @@ impl MovingSphere {
         center1: Point,
         time_range: Range<f64>,
         radius: f64,
-        material: Rc<dyn Material>,
+        material: Arc<dyn Material>,
     ) -> Self {
         Self {
             center0,
@@ -47,7 +48,7 @@ impl MovingSphere {
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, r: &Ray, range: Range<f64>) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, range: Range<f64>, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
         let oc = r.origin() - self.center(r.time());
 
         let a = r.direction().dot(r.direction());
@@ -70,13 +71,16 @@ impl Hittable for MovingSphere {
 
         let outward_normal = (r.at(root) - self.center(r.time())) / self.radius;
 
-        Some(HitRecord::new(
+        let mut rec = HitRecord::new(
             root,
             r,
             outward_normal,
             self.material.clone(),
             Self::get_uv(&outward_normal),
-        ))
+        );
+        let sin_theta = (1.0 - outward_normal.y * outward_normal.y).max(0.0).sqrt();
+        rec.footprint = r.footprint(root) / (PI * self.radius * (2.0 * sin_theta).clamp(1e-3, 1.0));
+        Some(rec)
     }
 
     fn bounding_box(&self, time_range: Range<f64>) -> Option<Aabb> {
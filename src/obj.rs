@@ -0,0 +1,107 @@
+use crate::material::Material;
+use crate::triangle::TriangleMesh;
+use crate::vec::{self, Point};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use wavefront_obj::obj;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "{}", err),
+            ObjError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<std::io::Error> for ObjError {
+    fn from(err: std::io::Error) -> Self {
+        ObjError::Io(err)
+    }
+}
+
+impl From<wavefront_obj::ParseError> for ObjError {
+    fn from(err: wavefront_obj::ParseError) -> Self {
+        ObjError::Parse {
+            line: err.line_number,
+            message: err.message,
+        }
+    }
+}
+
+pub fn load(path: &Path, material: Arc<dyn Material>) -> Result<Vec<Arc<TriangleMesh>>, ObjError> {
+    parse(&std::fs::read_to_string(path)?, material)
+}
+
+/// Builds one mesh per object in the file, all using `material`. Points and
+/// lines are skipped.
+pub fn parse(
+    source: &str,
+    material: Arc<dyn Material>,
+) -> Result<Vec<Arc<TriangleMesh>>, ObjError> {
+    let set = obj::parse(source)?;
+    Ok(set
+        .objects
+        .iter()
+        .map(|object| Arc::new(mesh(object, material.clone())))
+        .filter(|mesh| !mesh.is_empty())
+        .collect())
+}
+
+/// OBJ faces index positions, texture coordinates and normals separately;
+/// every distinct combination becomes one vertex of the mesh.
+fn mesh(object: &obj::Object, material: Arc<dyn Material>) -> TriangleMesh {
+    let mut vertices = HashMap::new();
+    let mut corners: Vec<obj::VTNIndex> = Vec::new();
+    let mut indices = Vec::new();
+
+    let mut index_of = |corner: obj::VTNIndex| {
+        *vertices.entry(corner).or_insert_with(|| {
+            corners.push(corner);
+            corners.len() - 1
+        })
+    };
+
+    for geometry in object.geometry.iter() {
+        for shape in geometry.shapes.iter() {
+            if let obj::Primitive::Triangle(a, b, c) = shape.primitive {
+                indices.push([index_of(a), index_of(b), index_of(c)]);
+            }
+        }
+    }
+
+    let positions = corners
+        .iter()
+        .map(|&(v, _, _)| {
+            let v = object.vertices[v];
+            Point::new(v.x, v.y, v.z)
+        })
+        .collect();
+
+    // Normals and uvs are only kept if every vertex has one.
+    let uvs = corners
+        .iter()
+        .map(|&(_, t, _)| t.map(|t| (object.tex_vertices[t].u, object.tex_vertices[t].v)))
+        .collect();
+    let normals = corners
+        .iter()
+        .map(|&(_, _, n)| {
+            n.map(|n| {
+                let n = object.normals[n];
+                vec::Vec::new(n.x, n.y, n.z)
+            })
+        })
+        .collect();
+
+    TriangleMesh::new(positions, normals, uvs, indices, material)
+}
@@ -0,0 +1,98 @@
+use crate::vec::{self, Point};
+use rand::{prelude::*, seq::SliceRandom};
+
+const POINT_COUNT: usize = 256;
+
+/// Ken Perlin's gradient noise. Every lattice point gets a random unit
+/// gradient; noise at a point blends the gradients of the eight surrounding
+/// lattice points with Hermite-smoothed trilinear interpolation.
+///
+/// The tables come from their own generator so that the same seed always
+/// gives the same pattern, whatever the renderer has drawn before.
+pub struct Perlin {
+    gradients: Vec<vec::Vec>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                vec::Vec::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .try_normalize(1e-8)
+                .unwrap_or_else(vec::Vec::x)
+            })
+            .collect();
+
+        let permutation = |rng: &mut SmallRng| {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(rng);
+            p
+        };
+
+        Self {
+            gradients,
+            perm_x: permutation(&mut rng),
+            perm_y: permutation(&mut rng),
+            perm_z: permutation(&mut rng),
+        }
+    }
+
+    /// Noise in about `-1.0..1.0`, zero at every lattice point.
+    pub fn noise(&self, p: &Point) -> f64 {
+        let floor = p.map(f64::floor);
+        let (u, v, w) = (p.x - floor.x, p.y - floor.y, p.z - floor.z);
+        let (i, j, k) = (floor.x as i64, floor.y as i64, floor.z as i64);
+
+        // Hermite smoothing hides the lattice.
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradient(i + di, j + dj, k + dk);
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = vec::Vec::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * gradient.dot(&weight);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Sum of `octaves` layers of noise, each at twice the frequency and half
+    /// the amplitude of the previous one. Always positive.
+    pub fn turbulence(&self, p: &Point, octaves: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = *p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accum += weight * self.noise(&p);
+            weight *= 0.5;
+            p *= 2.0;
+        }
+        accum.abs()
+    }
+
+    fn gradient(&self, i: i64, j: i64, k: i64) -> &vec::Vec {
+        let mask = POINT_COUNT as i64 - 1;
+        &self.gradients[self.perm_x[(i & mask) as usize]
+            ^ self.perm_y[(j & mask) as usize]
+            ^ self.perm_z[(k & mask) as usize]]
+    }
+}
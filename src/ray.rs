@@ -1,14 +1,29 @@
 use crate::vec::{Point, Vec};
 
+#[derive(Clone)]
 pub struct Ray {
     orig: Point,
     dir: Vec,
     time: f64,
+    spread: f64,
 }
 
 impl Ray {
     pub fn new(orig: Vec, dir: Vec, time: f64) -> Ray {
-        Ray { orig, dir, time }
+        Ray {
+            orig,
+            dir,
+            time,
+            spread: 0.0,
+        }
+    }
+
+    /// Makes the ray the axis of a cone widening by `spread` per unit of
+    /// distance travelled, which estimates the area a pixel sees for texture
+    /// filtering. Rays start as infinitely thin lines.
+    pub fn with_spread(mut self, spread: f64) -> Ray {
+        self.spread = spread;
+        self
     }
 
     pub fn at(&self, t: f64) -> Vec {
@@ -30,4 +45,13 @@ impl Ray {
     pub fn time(&self) -> f64 {
         self.time
     }
+
+    pub fn spread(&self) -> f64 {
+        self.spread
+    }
+
+    /// The width of the ray's cone at `t`.
+    pub fn footprint(&self, t: f64) -> f64 {
+        self.spread * t * self.dir.norm()
+    }
 }
@@ -0,0 +1,209 @@
+use crate::aabb::Aabb;
+use crate::hittable::{self, HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec::{Point, Vec};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// How far the bounding boxes of the rectangles extend off their plane, so
+/// that the boxes never have zero width.
+const PADDING: f64 = 0.0001;
+
+/// A rectangle in the plane `z = k`.
+pub struct XyRect {
+    x: Range<f64>,
+    y: Range<f64>,
+    k: f64,
+    material: Arc<dyn Material>,
+}
+
+impl XyRect {
+    pub fn new(x: Range<f64>, y: Range<f64>, k: f64, material: Arc<dyn Material>) -> Self {
+        Self { x, y, k, material }
+    }
+}
+
+impl Hittable for XyRect {
+    fn hit(&self, r: &Ray, range: Range<f64>, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        let t = (self.k - r.origin().z) / r.direction().z;
+        if !range.contains(&t) {
+            return None;
+        }
+
+        let x = r.origin().x + t * r.direction().x;
+        let y = r.origin().y + t * r.direction().y;
+        if !self.x.contains(&x) || !self.y.contains(&y) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(
+            t,
+            r,
+            Vec::new(0.0, 0.0, 1.0),
+            self.material.clone(),
+            (
+                (x - self.x.start) / (self.x.end - self.x.start),
+                (y - self.y.start) / (self.y.end - self.y.start),
+            ),
+        );
+        rec.footprint = r.footprint(t) / (self.x.end - self.x.start).min(self.y.end - self.y.start);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point::new(self.x.start, self.y.start, self.k - PADDING),
+            Point::new(self.x.end, self.y.end, self.k + PADDING),
+        ))
+    }
+
+    fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<Vec> {
+        let (u, v) = sampler.get_2d();
+        Some(Point::new(lerp(&self.x, u), lerp(&self.y, v), self.k) - origin)
+    }
+
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        match self.hit(r, 0.001..f64::INFINITY, sampler) {
+            Some(rec) => {
+                let area = (self.x.end - self.x.start) * (self.y.end - self.y.start);
+                hittable::area_to_solid_angle_pdf(r, rec.t, &Vec::new(0.0, 0.0, 1.0), area)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// A rectangle in the plane `y = k`.
+pub struct XzRect {
+    x: Range<f64>,
+    z: Range<f64>,
+    k: f64,
+    material: Arc<dyn Material>,
+}
+
+impl XzRect {
+    pub fn new(x: Range<f64>, z: Range<f64>, k: f64, material: Arc<dyn Material>) -> Self {
+        Self { x, z, k, material }
+    }
+}
+
+impl Hittable for XzRect {
+    fn hit(&self, r: &Ray, range: Range<f64>, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        let t = (self.k - r.origin().y) / r.direction().y;
+        if !range.contains(&t) {
+            return None;
+        }
+
+        let x = r.origin().x + t * r.direction().x;
+        let z = r.origin().z + t * r.direction().z;
+        if !self.x.contains(&x) || !self.z.contains(&z) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(
+            t,
+            r,
+            Vec::new(0.0, 1.0, 0.0),
+            self.material.clone(),
+            (
+                (x - self.x.start) / (self.x.end - self.x.start),
+                (z - self.z.start) / (self.z.end - self.z.start),
+            ),
+        );
+        rec.footprint = r.footprint(t) / (self.x.end - self.x.start).min(self.z.end - self.z.start);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point::new(self.x.start, self.k - PADDING, self.z.start),
+            Point::new(self.x.end, self.k + PADDING, self.z.end),
+        ))
+    }
+
+    fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<Vec> {
+        let (u, v) = sampler.get_2d();
+        Some(Point::new(lerp(&self.x, u), self.k, lerp(&self.z, v)) - origin)
+    }
+
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        match self.hit(r, 0.001..f64::INFINITY, sampler) {
+            Some(rec) => {
+                let area = (self.x.end - self.x.start) * (self.z.end - self.z.start);
+                hittable::area_to_solid_angle_pdf(r, rec.t, &Vec::new(0.0, 1.0, 0.0), area)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// A rectangle in the plane `x = k`.
+pub struct YzRect {
+    y: Range<f64>,
+    z: Range<f64>,
+    k: f64,
+    material: Arc<dyn Material>,
+}
+
+impl YzRect {
+    pub fn new(y: Range<f64>, z: Range<f64>, k: f64, material: Arc<dyn Material>) -> Self {
+        Self { y, z, k, material }
+    }
+}
+
+impl Hittable for YzRect {
+    fn hit(&self, r: &Ray, range: Range<f64>, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        let t = (self.k - r.origin().x) / r.direction().x;
+        if !range.contains(&t) {
+            return None;
+        }
+
+        let y = r.origin().y + t * r.direction().y;
+        let z = r.origin().z + t * r.direction().z;
+        if !self.y.contains(&y) || !self.z.contains(&z) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(
+            t,
+            r,
+            Vec::new(1.0, 0.0, 0.0),
+            self.material.clone(),
+            (
+                (y - self.y.start) / (self.y.end - self.y.start),
+                (z - self.z.start) / (self.z.end - self.z.start),
+            ),
+        );
+        rec.footprint = r.footprint(t) / (self.y.end - self.y.start).min(self.z.end - self.z.start);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point::new(self.k - PADDING, self.y.start, self.z.start),
+            Point::new(self.k + PADDING, self.y.end, self.z.end),
+        ))
+    }
+
+    fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<Vec> {
+        let (u, v) = sampler.get_2d();
+        Some(Point::new(self.k, lerp(&self.y, u), lerp(&self.z, v)) - origin)
+    }
+
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        match self.hit(r, 0.001..f64::INFINITY, sampler) {
+            Some(rec) => {
+                let area = (self.y.end - self.y.start) * (self.z.end - self.z.start);
+                hittable::area_to_solid_angle_pdf(r, rec.t, &Vec::new(1.0, 0.0, 0.0), area)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// The point a fraction `t` of the way through `range`.
+fn lerp(range: &Range<f64>, t: f64) -> f64 {
+    range.start + t * (range.end - range.start)
+}
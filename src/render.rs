@@ -0,0 +1,657 @@
+use crate::camera::{self, Camera};
+use crate::filter::{Filter, FilterKind};
+use crate::integrator::Integrator;
+use crate::sampler::{Sampler, SamplerKind};
+use crate::scene::Scene;
+use crate::util;
+use crate::vec::Color;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct RenderSettings {
+    pub image_width: u32,
+    pub image_height: u32,
+    /// Samples every pixel takes, or at most takes with `error_threshold`.
+    pub samples_per_pixel: u32,
+    /// Lets pixels stop sampling early once the standard error of their
+    /// mean luminance is at most this fraction of the mean. Checked after
+    /// 16, 32, 64 and so on samples, so that samplers that stratify
+    /// power-of-two prefixes stop on one.
+    pub error_threshold: Option<f64>,
+    pub tile_size: u32,
+    pub threads: usize,
+    /// Where the numbers each pixel sample consumes come from.
+    pub sampler: SamplerKind,
+    /// How samples are weighted into the pixels around them.
+    pub filter: FilterKind,
+    /// The filter's radius in pixels, or its default if `None`. Has to be
+    /// finite.
+    pub filter_radius: Option<f64>,
+    /// Every pixel sample draws from its own generator, seeded from this
+    /// and its coordinates, so the same seed always gives the same image
+    /// whatever the number of threads.
+    pub seed: u64,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            image_width: 400,
+            image_height: 266,
+            samples_per_pixel: 100,
+            error_threshold: None,
+            tile_size: 16,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            sampler: SamplerKind::Sobol,
+            filter: FilterKind::Box,
+            filter_radius: None,
+            seed: 0,
+        }
+    }
+}
+
+/// Linear radiance estimates, unclamped and stored top row first.
+#[derive(Clone)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    /// Panics if `width * height` doesn't fit in a `usize`.
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .expect("framebuffer size overflows");
+        Self {
+            width,
+            height,
+            pixels: vec![Color::zeros(); len],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The color at `(x, y)`, counting rows from the top.
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+}
+
+/// What [`render`] produces.
+pub struct RenderOutput {
+    pub image: Framebuffer,
+    /// How many samples every pixel took, over `samples_per_pixel`, as a
+    /// gray image.
+    pub sample_map: Framebuffer,
+}
+
+/// How far a render has got, as handed to its progress callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// A tile of a [`render`] has finished, leaving this many.
+    Tiles { remaining: usize },
+    /// A pass of a [`render_progressive`] has finished, leaving this many.
+    Passes { remaining: u32 },
+}
+
+/// Sums of filter weighted samples over a rectangle of pixels, `x0..x1`
+/// by `y0..y1` as in [`Tile`], together with the sums of their weights and
+/// the number of samples taken in each pixel.
+struct Film {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+    samples: Vec<u32>,
+}
+
+impl Film {
+    fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        let len = (x1 - x0) as usize * (y1 - y0) as usize;
+        Self {
+            x0,
+            y0,
+            x1,
+            y1,
+            sums: vec![Color::zeros(); len],
+            weights: vec![0.0; len],
+            samples: vec![0; len],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y - self.y0) as usize * (self.x1 - self.x0) as usize + (x - self.x0) as usize
+    }
+
+    /// Adds `color`, seen at `(x, y)` on the image, to every pixel of the
+    /// film whose center is within `filter`'s radius of it. Only one of two
+    /// pixels exactly the radius away gets it, so that a box filter of
+    /// radius 0.5 puts every sample into exactly one pixel.
+    fn splat(&mut self, x: f64, y: f64, color: Color, filter: &dyn Filter) {
+        let radius = filter.radius();
+        let first = |c: f64, min: u32| ((c - 0.5 - radius).floor() + 1.0).max(min as f64) as u32;
+        let last = |c: f64, max: u32| ((c - 0.5 + radius).floor() + 1.0).min(max as f64) as u32;
+        for py in first(y, self.y0)..last(y, self.y1) {
+            for px in first(x, self.x0)..last(x, self.x1) {
+                let weight = filter.evaluate(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+                if weight != 0.0 {
+                    let index = self.index(px, py);
+                    self.sums[index] += weight * color;
+                    self.weights[index] += weight;
+                }
+            }
+        }
+    }
+
+    /// Adds the samples splatted into `other`, which must lie within this
+    /// film.
+    fn add(&mut self, other: &Film) {
+        for y in other.y0..other.y1 {
+            for x in other.x0..other.x1 {
+                let (to, from) = (self.index(x, y), other.index(x, y));
+                self.sums[to] += other.sums[from];
+                self.weights[to] += other.weights[from];
+                self.samples[to] += other.samples[from];
+            }
+        }
+    }
+
+    /// The weighted average of every pixel. Pixels without any weight,
+    /// which negative filter lobes can cause, stay black.
+    fn resolve(&self) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.x1 - self.x0, self.y1 - self.y0);
+        for ((pixel, sum), &weight) in framebuffer
+            .pixels_mut()
+            .iter_mut()
+            .zip(&self.sums)
+            .zip(&self.weights)
+        {
+            if weight > 0.0 {
+                *pixel = sum / weight;
+            }
+        }
+        framebuffer
+    }
+
+    /// The number of samples taken in every pixel over `max_samples`.
+    fn sample_map(&self, max_samples: u32) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.x1 - self.x0, self.y1 - self.y0);
+        for (pixel, &samples) in framebuffer.pixels_mut().iter_mut().zip(&self.samples) {
+            *pixel = Color::from_element(samples as f64 / max_samples as f64);
+        }
+        framebuffer
+    }
+}
+
+/// Pixels stop sampling early no sooner than this.
+const MIN_ADAPTIVE_SAMPLES: u32 = 16;
+
+/// Pixels darker than this count as this bright when judging their noise,
+/// so that black pixels don't take every sample for noise nobody can see.
+const MIN_ADAPTIVE_MEAN: f64 = 0.01;
+
+/// The running mean and variance of a pixel's sample luminances, updated
+/// with Welford's algorithm.
+#[derive(Default)]
+struct PixelStats {
+    count: u32,
+    mean: f64,
+    m2: f64,
+    /// Whether the pixel has taken enough samples.
+    converged: bool,
+}
+
+impl PixelStats {
+    fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// The standard error of the mean over the mean. Needs two samples.
+    fn relative_error(&self) -> f64 {
+        let variance = self.m2 / (self.count - 1) as f64;
+        (variance / self.count as f64).sqrt() / self.mean.max(MIN_ADAPTIVE_MEAN)
+    }
+
+    /// Marks the pixel converged if `threshold` is met at a sample count
+    /// where that is checked.
+    fn check(&mut self, threshold: f64) {
+        self.converged |= self.count >= MIN_ADAPTIVE_SAMPLES
+            && self.count.is_power_of_two()
+            && self.relative_error() <= threshold;
+    }
+}
+
+fn luminance(color: &Color) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// A rectangle of pixels, `x0..x1` by `y0..y1`, with `y` counted from the top.
+struct Tile {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+fn tiles(width: u32, height: u32, tile_size: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y0 in (0..height).step_by(tile_size as usize) {
+        for x0 in (0..width).step_by(tile_size as usize) {
+            tiles.push(Tile {
+                x0,
+                y0,
+                x1: (x0 + tile_size).min(width),
+                y1: (y0 + tile_size).min(height),
+            });
+        }
+    }
+    tiles
+}
+
+/// Everything the workers rendering a pass share.
+struct Job<'a> {
+    scene: &'a Scene,
+    integrator: &'a dyn Integrator,
+    settings: &'a RenderSettings,
+    cam: Camera,
+    filter: Box<dyn Filter>,
+    tiles: Vec<Tile>,
+    /// The statistics of every tile's pixels, row by row, carried over from
+    /// pass to pass.
+    stats: Vec<Mutex<Vec<PixelStats>>>,
+}
+
+impl<'a> Job<'a> {
+    fn new(scene: &'a Scene, integrator: &'a dyn Integrator, settings: &'a RenderSettings) -> Self {
+        let tiles = tiles(
+            settings.image_width,
+            settings.image_height,
+            settings.tile_size,
+        );
+        let stats = tiles
+            .iter()
+            .map(|tile| {
+                let len = (tile.x1 - tile.x0) * (tile.y1 - tile.y0);
+                Mutex::new((0..len).map(|_| PixelStats::default()).collect())
+            })
+            .collect();
+        Self {
+            scene,
+            integrator,
+            settings,
+            cam: scene
+                .camera
+                .build(settings.image_width as f64 / settings.image_height as f64),
+            filter: settings.filter.build(settings.filter_radius),
+            tiles,
+            stats,
+        }
+    }
+
+    /// Renders `samples` of the pixels in tile `index` into a film reaching
+    /// the filter's radius past the tile, clipped to the image. Pixels that
+    /// have converged are skipped.
+    fn render_tile(&self, index: usize, samples: Range<u32>, sampler: &mut dyn Sampler) -> Film {
+        let (tile, settings, filter) = (&self.tiles[index], self.settings, &*self.filter);
+        let mut tile_stats = self.stats[index].lock().unwrap();
+        let spread = self.cam.pixel_spread(settings.image_height);
+        let pad = filter.radius().ceil() as u32;
+        let mut film = Film::new(
+            tile.x0.saturating_sub(pad),
+            tile.y0.saturating_sub(pad),
+            tile.x1.saturating_add(pad).min(settings.image_width),
+            tile.y1.saturating_add(pad).min(settings.image_height),
+        );
+        let mut pixels = tile_stats.iter_mut();
+        for y in tile.y0..tile.y1 {
+            let j = settings.image_height - 1 - y;
+            for i in tile.x0..tile.x1 {
+                let stats = pixels.next().unwrap();
+                let mut taken = 0;
+                for sample in samples.clone() {
+                    if stats.converged {
+                        break;
+                    }
+                    util::seed(util::hash(
+                        settings.seed,
+                        &[i as u64, y as u64, sample as u64],
+                    ));
+                    sampler.start_pixel_sample((i, y), sample);
+                    let (du, dv) = sampler.get_2d();
+                    let u = (i as f64 + du) / settings.image_width as f64;
+                    let v = (j as f64 + dv) / settings.image_height as f64;
+                    let r = self
+                        .cam
+                        .get_ray(u, v, camera::SHUTTER, sampler)
+                        .with_spread(spread);
+                    let color = self.integrator.radiance(&r, self.scene, sampler);
+                    // `v` counts up from the bottom of the pixel, `y` down
+                    // from the top of the image.
+                    film.splat(i as f64 + du, y as f64 + 1.0 - dv, color, filter);
+                    taken += 1;
+
+                    stats.add(luminance(&color));
+                    if let Some(threshold) = settings.error_threshold {
+                        stats.check(threshold);
+                    }
+                }
+                let index = film.index(i, y);
+                film.samples[index] = taken;
+            }
+        }
+        film
+    }
+
+    /// Renders `samples` of every pixel on `settings.threads` workers, each
+    /// with its own `settings.sampler`. Tiles are handed out through a
+    /// shared counter and sent back to the calling thread, until they run
+    /// out or `stop` is set. Samples near a tile's edge also count towards
+    /// pixels of the neighbouring tiles, so the tiles are only added up once
+    /// all are done, always in the same order, to keep the image the same
+    /// whatever the number of threads.
+    fn render_pass(
+        &self,
+        samples: Range<u32>,
+        stop: &AtomicBool,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Film {
+        let settings = self.settings;
+        let next_tile = AtomicUsize::new(0);
+        let mut films: Vec<Option<Film>> = self.tiles.iter().map(|_| None).collect();
+
+        thread::scope(|s| {
+            let (tx, rx) = mpsc::channel();
+
+            for _ in 0..settings.threads.max(1) {
+                let tx = tx.clone();
+                let (samples, next_tile) = (samples.clone(), &next_tile);
+                s.spawn(move || {
+                    let mut sampler = settings
+                        .sampler
+                        .build(settings.samples_per_pixel, settings.seed);
+                    loop {
+                        let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        if index >= self.tiles.len() || stop.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let film = self.render_tile(index, samples.clone(), &mut *sampler);
+                        if tx.send((index, film)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            for (done, (index, film)) in rx.iter().enumerate() {
+                films[index] = Some(film);
+                progress(Progress::Tiles {
+                    remaining: self.tiles.len() - done - 1,
+                });
+            }
+        });
+
+        let mut film = Film::new(0, 0, settings.image_width, settings.image_height);
+        for tile_film in films.iter().flatten() {
+            film.add(tile_film);
+        }
+        film
+    }
+
+    fn output(&self, film: &Film) -> RenderOutput {
+        RenderOutput {
+            image: film.resolve(),
+            sample_map: film.sample_map(self.settings.samples_per_pixel),
+        }
+    }
+}
+
+/// Renders the image, every pixel sample colored by `integrator`, calling
+/// `progress` on the calling thread as tiles finish.
+pub fn render(
+    scene: &Scene,
+    integrator: &dyn Integrator,
+    settings: &RenderSettings,
+    mut progress: impl FnMut(Progress),
+) -> RenderOutput {
+    let job = Job::new(scene, integrator, settings);
+    let stop = AtomicBool::new(false);
+    let film = job.render_pass(0..settings.samples_per_pixel, &stop, &mut progress);
+    job.output(&film)
+}
+
+/// When [`render_progressive`] takes snapshots, whichever comes first.
+pub struct Snapshots {
+    /// After every this many passes.
+    pub passes: Option<u32>,
+    /// Once this long has gone by since the last snapshot.
+    pub interval: Option<Duration>,
+}
+
+/// Renders the image like [`render`], but in passes of one sample per pixel,
+/// `settings.samples_per_pixel` of them, handing the estimate so far to
+/// `snapshot` as `snapshots` asks. Once `stop` is set, the tiles being
+/// rendered are finished and the render ends early. `progress` hears of
+/// every finished pass. Returns the final estimate, which isn't passed to
+/// `snapshot`.
+pub fn render_progressive(
+    scene: &Scene,
+    integrator: &dyn Integrator,
+    settings: &RenderSettings,
+    snapshots: &Snapshots,
+    stop: &AtomicBool,
+    mut snapshot: impl FnMut(&RenderOutput),
+    mut progress: impl FnMut(Progress),
+) -> RenderOutput {
+    let job = Job::new(scene, integrator, settings);
+    let mut film = Film::new(0, 0, settings.image_width, settings.image_height);
+    let mut last_snapshot = Instant::now();
+
+    for pass in 0..settings.samples_per_pixel {
+        film.add(&job.render_pass(pass..pass + 1, stop, &mut |_| {}));
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        progress(Progress::Passes {
+            remaining: settings.samples_per_pixel - pass - 1,
+        });
+
+        let passes_due = snapshots.passes.is_some_and(|n| (pass + 1) % n == 0);
+        let time_due = snapshots
+            .interval
+            .is_some_and(|interval| last_snapshot.elapsed() >= interval);
+        if (passes_due || time_due) && pass + 1 < settings.samples_per_pixel {
+            snapshot(&job.output(&film));
+            last_snapshot = Instant::now();
+        }
+    }
+
+    job.output(&film)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterKind;
+    use crate::integrator::PathTracer;
+    use crate::scenes;
+
+    #[test]
+    fn box_filter_splats_into_one_pixel() {
+        let filter = FilterKind::Box.build(None);
+        let mut film = Film::new(0, 0, 3, 3);
+        // On the edges between pixels, too.
+        for (x, y) in [(1.3, 0.7), (1.0, 1.5), (2.0, 2.0), (0.0, 0.0)] {
+            film.splat(x, y, Color::new(1.0, 2.0, 3.0), filter.as_ref());
+        }
+        assert_eq!(film.weights.iter().sum::<f64>(), 4.0);
+        assert!(film.weights.iter().all(|&w| w == 0.0 || w == 1.0));
+        assert_eq!(film.weights[film.index(1, 0)], 1.0);
+        assert_eq!(film.sums[film.index(1, 0)], Color::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn splats_stay_inside_the_film() {
+        let filter = FilterKind::Lanczos.build(None);
+        let mut film = Film::new(4, 8, 6, 10);
+        film.splat(4.2, 8.9, Color::from_element(1.0), filter.as_ref());
+        film.splat(0.5, 0.5, Color::from_element(1.0), filter.as_ref());
+        assert!(film.weights.iter().any(|&w| w != 0.0));
+    }
+
+    #[test]
+    fn resolve_normalizes_by_weight() {
+        let color = Color::new(0.25, 0.5, 1.0);
+        for kind in FilterKind::NAMES
+            .iter()
+            .filter_map(|n| FilterKind::from_name(n))
+        {
+            let filter = kind.build(None);
+            let mut film = Film::new(0, 0, 8, 8);
+            // A jittered grid of samples of one color.
+            for i in 0..64 * 16 {
+                let (x, y) = ((i % 32) as f64 + 0.37, (i / 32) as f64 + 0.61);
+                film.splat(x / 4.0, y / 4.0, color, filter.as_ref());
+            }
+            for pixel in film.resolve().pixels() {
+                assert!((pixel - color).norm() < 1e-9, "{:?}: {:?}", kind, pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn add_merges_tiles() {
+        let filter = FilterKind::Tent.build(None);
+        let mut whole = Film::new(0, 0, 4, 2);
+        let mut left = Film::new(0, 0, 2, 2);
+        let mut right = Film::new(2, 0, 4, 2);
+        for (x, y) in [(0.5, 0.5), (1.9, 1.2), (2.1, 0.3), (3.5, 1.5)] {
+            let color = Color::new(x, y, 1.0);
+            whole.splat(x, y, color, filter.as_ref());
+            left.splat(x, y, color, filter.as_ref());
+            right.splat(x, y, color, filter.as_ref());
+        }
+        let mut merged = Film::new(0, 0, 4, 2);
+        merged.add(&left);
+        merged.add(&right);
+        assert_eq!(merged.weights, whole.weights);
+        for (a, b) in merged.sums.iter().zip(&whole.sums) {
+            assert!((a - b).norm() < 1e-12);
+        }
+    }
+
+    fn settings(threads: usize) -> RenderSettings {
+        RenderSettings {
+            image_width: 20,
+            image_height: 20,
+            samples_per_pixel: 4,
+            tile_size: 8,
+            threads,
+            ..Default::default()
+        }
+    }
+
+    fn render_cornell(settings: &RenderSettings) -> RenderOutput {
+        render(
+            &scenes::cornell_box(0),
+            &PathTracer::new(8, 3),
+            settings,
+            |_| {},
+        )
+    }
+
+    #[test]
+    fn thread_count_does_not_change_the_image() {
+        let samplers = [SamplerKind::Independent, SamplerKind::Sobol];
+        let filters = [FilterKind::Box, FilterKind::Mitchell];
+        for (sampler, filter) in samplers.into_iter().zip(filters) {
+            let single = RenderSettings {
+                sampler,
+                filter,
+                ..settings(1)
+            };
+            let expected = render_cornell(&single);
+            let found = render_cornell(&RenderSettings {
+                threads: 4,
+                ..single
+            });
+            assert_eq!(found.image.pixels(), expected.image.pixels());
+            assert!(expected.image.pixels().iter().any(|p| p.max() > 0.0));
+        }
+    }
+
+    #[test]
+    fn seed_changes_the_image() {
+        let expected = render_cornell(&settings(2));
+        let found = render_cornell(&RenderSettings {
+            seed: 1,
+            ..settings(2)
+        });
+        assert_ne!(found.image.pixels(), expected.image.pixels());
+    }
+
+    #[test]
+    fn thread_count_does_not_change_adaptive_sampling() {
+        let single = RenderSettings {
+            samples_per_pixel: 64,
+            error_threshold: Some(0.5),
+            ..settings(1)
+        };
+        let expected = render_cornell(&single);
+        let found = render_cornell(&RenderSettings {
+            threads: 3,
+            ..single
+        });
+        assert_eq!(found.image.pixels(), expected.image.pixels());
+        assert_eq!(found.sample_map.pixels(), expected.sample_map.pixels());
+        // Some pixels stopped early.
+        assert!(expected.sample_map.pixels().iter().any(|p| p.x < 1.0));
+    }
+
+    #[test]
+    fn progressive_render_matches_one_pass() {
+        let settings = settings(2);
+        let expected = render_cornell(&settings);
+        let mut snapshots = 0;
+        let found = render_progressive(
+            &scenes::cornell_box(0),
+            &PathTracer::new(8, 3),
+            &settings,
+            &Snapshots {
+                passes: Some(1),
+                interval: None,
+            },
+            &AtomicBool::new(false),
+            |_| snapshots += 1,
+            |_| {},
+        );
+        assert_eq!(snapshots, settings.samples_per_pixel - 1);
+        for (a, b) in found.image.pixels().iter().zip(expected.image.pixels()) {
+            assert!((a - b).norm() <= 1e-9 * b.norm().max(1.0));
+        }
+    }
+}
@@ -0,0 +1,473 @@
+//! Samplers hand out the numbers in `0..1` that a pixel sample turns into
+//! a position on the image, a point on the lens, scatter directions and
+//! light samples. Each call takes the next dimension of the sample, so the
+//! numbers are only well distributed if every pixel sample asks for them
+//! in the same order.
+//!
+//! Apart from [`Independent`], samplers spread the samples of a pixel
+//! evenly over every dimension, which makes the noise go away faster than
+//! with independent random numbers. All of them are decorrelated between
+//! pixels by hashing the pixel and the render seed, so a seed always gives
+//! the same image.
+
+use crate::util;
+
+pub trait Sampler: Send {
+    /// Starts sample `index` of `pixel`, counting dimensions from zero
+    /// again.
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32);
+
+    /// The next dimension of the current sample.
+    fn get_1d(&mut self) -> f64;
+
+    /// The next two dimensions of the current sample, meant to be used
+    /// together, like the two coordinates of a point on the lens.
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+/// Where a sampler is: which sample of which pixel, and how many
+/// dimensions it has handed out.
+struct Position {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+}
+
+impl Position {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    /// Takes the next dimension.
+    fn next(&mut self) -> u32 {
+        self.dimension += 1;
+        self.dimension - 1
+    }
+
+    /// A hash of the pixel, `dimension` and `salt`, the same for every
+    /// sample of the pixel.
+    fn hash(&self, dimension: u32, salt: u64) -> u64 {
+        util::hash(
+            self.seed,
+            &[
+                self.pixel.0 as u64,
+                self.pixel.1 as u64,
+                dimension as u64,
+                salt,
+            ],
+        )
+    }
+}
+
+/// Plain uniform random numbers from [`util::random_f64`], which the
+/// renderer reseeds for every pixel sample.
+pub struct Independent;
+
+impl Sampler for Independent {
+    fn start_pixel_sample(&mut self, _pixel: (u32, u32), _index: u32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        util::random_f64()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (util::random_f64(), util::random_f64())
+    }
+}
+
+/// Splits every dimension into `samples_per_pixel` strata, and every pair
+/// of dimensions into a grid of about as many cells, and puts each sample
+/// of a pixel into a different one, jittered inside it. Which sample gets
+/// which stratum is shuffled per pixel and dimension. Samples past
+/// `samples_per_pixel` start over with the same strata.
+pub struct Stratified {
+    samples_per_pixel: u32,
+    position: Position,
+}
+
+impl Stratified {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            position: Position::new(seed),
+        }
+    }
+
+    /// The stratum out of `count` that the current sample gets in
+    /// `dimension`.
+    fn stratum(&self, dimension: u32, count: u32) -> u32 {
+        let seed = self.position.hash(dimension, 0) as u32;
+        permute(self.position.index % count, count, seed)
+    }
+}
+
+impl Sampler for Stratified {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.position.next();
+        let n = self.samples_per_pixel;
+        (self.stratum(dimension, n) as f64 + util::random_f64()) / n as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.position.next();
+        self.position.next();
+        let nx = (self.samples_per_pixel as f64).sqrt().ceil() as u32;
+        let ny = self.samples_per_pixel.div_ceil(nx);
+        let cell = self.stratum(dimension, nx * ny);
+        (
+            ((cell % nx) as f64 + util::random_f64()) / nx as f64,
+            ((cell / nx) as f64 + util::random_f64()) / ny as f64,
+        )
+    }
+}
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// The Halton sequence, dimension `d` being the radical inverse of the
+/// sample index in the `d`-th prime, shifted by a random amount per pixel
+/// and dimension. Higher primes fill the unit interval slowly, so
+/// dimensions past the first 32 get independent random numbers instead.
+pub struct Halton {
+    position: Position,
+}
+
+impl Halton {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            position: Position::new(seed),
+        }
+    }
+}
+
+impl Sampler for Halton {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.position.next();
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => {
+                let shift = hash_to_f64(self.position.hash(dimension, 0));
+                (radical_inverse(base, self.position.index) + shift).fract()
+            }
+            None => util::random_f64(),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// The first two dimensions of the Sobol sequence, Owen scrambled, for
+/// every pair of dimensions, following Burley's "Practical Hash-based Owen
+/// Scrambling". Each pair gets its own shuffle of the sample order and its
+/// own scramble, both per pixel, so pairs don't correlate with each other
+/// while every power-of-two prefix of a pixel's samples stays stratified.
+pub struct Sobol {
+    position: Position,
+}
+
+impl Sobol {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            position: Position::new(seed),
+        }
+    }
+
+    /// The current sample's index after the shuffle for `dimension`.
+    fn shuffled_index(&self, dimension: u32) -> u32 {
+        owen_scramble(self.position.index, self.position.hash(dimension, 0) as u32)
+    }
+}
+
+impl Sampler for Sobol {
+    fn start_pixel_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.position.start(pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.position.next();
+        let index = self.shuffled_index(dimension);
+        let x = owen_scramble(sobol_0(index), self.position.hash(dimension, 1) as u32);
+        fraction_to_f64(x)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.position.next();
+        self.position.next();
+        let index = self.shuffled_index(dimension);
+        let x = owen_scramble(sobol_0(index), self.position.hash(dimension, 1) as u32);
+        let y = owen_scramble(sobol_1(index), self.position.hash(dimension, 2) as u32);
+        (fraction_to_f64(x), fraction_to_f64(y))
+    }
+}
+
+/// The samplers the command line can pick by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    pub const NAMES: &'static [&'static str] = &["independent", "stratified", "halton", "sobol"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "independent" => Some(SamplerKind::Independent),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            _ => None,
+        }
+    }
+
+    /// Builds a sampler for pixels taking `samples_per_pixel` samples each,
+    /// scrambled by `seed`.
+    pub fn build(self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(Independent),
+            SamplerKind::Stratified => Box::new(Stratified::new(samples_per_pixel, seed)),
+            SamplerKind::Halton => Box::new(Halton::new(seed)),
+            SamplerKind::Sobol => Box::new(Sobol::new(seed)),
+        }
+    }
+}
+
+/// The digits of `index` in `base`, mirrored around the point.
+fn radical_inverse(base: u32, mut index: u32) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut scale = inv_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * scale;
+        index /= base;
+        scale *= inv_base;
+    }
+    result
+}
+
+/// The first dimension of the Sobol sequence, as a 32-bit fraction.
+fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// The second dimension of the Sobol sequence, as a 32-bit fraction.
+fn sobol_1(mut index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+/// An Owen scramble of the 32-bit fraction `x`: every bit is flipped or
+/// not depending on the bits above it.
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Laine and Karras' hash, in which every bit only depends on the bits
+/// below it.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+/// Element `i` of a random permutation of `0..count` picked by `seed`,
+/// after Kensler's "Correlated Multi-Jittered Sampling".
+fn permute(mut i: u32, count: u32, seed: u32) -> u32 {
+    let mut w = count - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < count {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % count
+}
+
+/// A 32-bit fraction as a float in `0..1`.
+fn fraction_to_f64(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
+
+/// A hash as a float in `0..1`.
+fn hash_to_f64(h: u64) -> f64 {
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first `count` samples of `pixel`, each as its first `dimensions`
+    /// 2D points.
+    fn samples(
+        sampler: &mut dyn Sampler,
+        pixel: (u32, u32),
+        count: u32,
+        dimensions: usize,
+    ) -> Vec<Vec<(f64, f64)>> {
+        (0..count)
+            .map(|index| {
+                sampler.start_pixel_sample(pixel, index);
+                (0..dimensions).map(|_| sampler.get_2d()).collect()
+            })
+            .collect()
+    }
+
+    /// Whether every `nx` by `ny` cell of the unit square holds exactly one
+    /// point.
+    fn one_per_cell(points: &[(f64, f64)], nx: usize, ny: usize) -> bool {
+        let mut cells = vec![0; nx * ny];
+        for &(x, y) in points {
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+            cells[(y * ny as f64) as usize * nx + (x * nx as f64) as usize] += 1;
+        }
+        cells.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn sobol_prefixes_are_stratified() {
+        let mut sampler = Sobol::new(7);
+        for pixel in [(0, 0), (13, 2)] {
+            let samples = samples(&mut sampler, pixel, 256, 4);
+            for dimension in 0..4 {
+                for log in 0..=8 {
+                    let n = 1 << log;
+                    let points: Vec<_> = samples[..n].iter().map(|s| s[dimension]).collect();
+                    // Every elementary interval of area 1 / n.
+                    for a in 0..=log {
+                        assert!(
+                            one_per_cell(&points, 1 << a, 1 << (log - a)),
+                            "pixel {:?}, dimension {}, {} samples, {}x{}",
+                            pixel,
+                            dimension,
+                            n,
+                            1 << a,
+                            1 << (log - a)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_scrambles_differ_between_pixels_and_dimensions() {
+        let mut sampler = Sobol::new(0);
+        let a = samples(&mut sampler, (0, 0), 4, 2);
+        let b = samples(&mut sampler, (1, 0), 4, 2);
+        assert_ne!(a, b);
+        assert_ne!(a[0][0], a[0][1]);
+        assert_eq!(a, samples(&mut sampler, (0, 0), 4, 2));
+    }
+
+    #[test]
+    fn stratified_fills_every_stratum() {
+        for spp in [1, 4, 9, 16] {
+            let n = (spp as f64).sqrt() as usize;
+            let mut sampler = Stratified::new(spp, 3);
+            let samples = samples(&mut sampler, (5, 8), spp, 3);
+            for dimension in 0..3 {
+                let points: Vec<_> = samples.iter().map(|s| s[dimension]).collect();
+                assert!(one_per_cell(&points, n, n));
+            }
+
+            let mut strata = vec![0; spp as usize];
+            for index in 0..spp {
+                sampler.start_pixel_sample((5, 8), index);
+                strata[(sampler.get_1d() * spp as f64) as usize] += 1;
+            }
+            assert!(strata.iter().all(|&count| count == 1));
+        }
+    }
+
+    #[test]
+    fn halton_first_dimensions_are_stratified() {
+        let mut sampler = Halton::new(11);
+        let mut strata = [0; 9];
+        for index in 0..9 {
+            sampler.start_pixel_sample((2, 3), index);
+            sampler.get_1d();
+            strata[(sampler.get_1d() * 9.0) as usize] += 1;
+        }
+        // Base 3, shifted, still puts one of 9 samples in each ninth.
+        assert!(strata.iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn radical_inverse_mirrors_digits() {
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn permute_is_a_permutation() {
+        for count in [1, 2, 7, 64, 100] {
+            for seed in [0, 1, 0xdead_beef] {
+                let mut seen = vec![false; count as usize];
+                for i in 0..count {
+                    seen[permute(i, count, seed) as usize] = true;
+                }
+                assert!(seen.iter().all(|&s| s));
+            }
+        }
+    }
+}
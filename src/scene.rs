@@ -0,0 +1,74 @@
+use crate::bvh::{Accelerator, BvhOptions};
+use crate::camera::{self, CameraSettings};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::ray::Ray;
+use crate::vec::Color;
+use std::sync::Arc;
+
+/// What rays that escape the scene see.
+#[derive(Clone, Debug)]
+pub enum Background {
+    Solid(Color),
+    /// Blends from `bottom` looking straight down to `top` looking up.
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    /// Black, leaving emissive materials as the only light.
+    None,
+}
+
+impl Background {
+    pub fn color(&self, r: &Ray) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let t = 0.5 * (r.unit_direction().y + 1.0);
+                bottom * (1.0 - t) + t * top
+            }
+            Background::None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl Default for Background {
+    /// The sky the renderer has always used.
+    fn default() -> Self {
+        Background::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+/// Everything the renderer needs to produce an image: the objects to
+/// intersect, the camera looking at them and the background behind them.
+pub struct Scene {
+    pub world: Arc<dyn Hittable>,
+    /// Emitters to sample directly. They must also be part of `world`;
+    /// lights missing here are still found, just with more noise.
+    pub lights: HittableList,
+    pub camera: CameraSettings,
+    pub background: Background,
+}
+
+impl Scene {
+    /// Puts `objects` in an [`Accelerator`] built with the default options.
+    pub fn new(objects: HittableList, camera: CameraSettings) -> Self {
+        Self::with_options(objects, camera, &BvhOptions::default())
+    }
+
+    pub fn with_options(
+        objects: HittableList,
+        camera: CameraSettings,
+        options: &BvhOptions,
+    ) -> Self {
+        Self {
+            world: Arc::new(Accelerator::new(objects.objects, camera::SHUTTER, options)),
+            lights: HittableList::new(),
+            camera,
+            background: Background::default(),
+        }
+    }
+}
@@ -0,0 +1,1091 @@
+//! Loader for `.rayt` scene description files.
+//!
+//! A scene file is a list of blocks. Each block starts with a keyword,
+//! optionally followed by a name and a type, and holds one field per line
+//! (`;` also ends a field):
+//!
+//! ```text
+//! # comments run to the end of the line
+//! camera {
+//!     lookfrom 13 2 3
+//!     lookat 0 0 0
+//!     vfov 20
+//!     aperture 0.1
+//! }
+//!
+//! texture ground_checker checker { odd 0.2 0.3 0.1; even 0.9 0.9 0.9 }
+//! material ground lambertian { albedo ground_checker }
+//! material glass dielectric { ir 1.5 }
+//! material lamp diffuse_light { emit 4 4 4 }
+//!
+//! background gradient { bottom 1 1 1; top 0.5 0.7 1 }
+//!
+//! sphere { center 0 -1000 0; radius 1000; material ground }
+//! sphere { center 0 1 0; radius 1; material glass }
+//! sphere { center 0 4 0; radius 0.5; material lamp }
+//! xz_rect { x -2 2; z -2 2; k 6; material lamp }
+//! box { min 2 0 2; max 3 1 3; material ground }
+//! mesh { file "teapot.obj"; material ground }
+//! ```
+//!
+//! Image textures take a `file` and optional `filter` (`nearest`,
+//! `bilinear` or the default `trilinear`) and `wrap` (the default `clamp`,
+//! `repeat` or `mirror`) fields:
+//!
+//! ```text
+//! texture earth image { file "earth.png"; wrap repeat }
+//! ```
+//!
+//! Procedural `noise`, `turbulence` and `marble` textures take optional
+//! `scale`, `seed` and (except `noise`) `octaves` fields, with `octaves`
+//! between 1 and 32.
+//!
+//! Fields that take a color accept either three numbers or the name of a
+//! texture declared earlier in the file.
+//!
+//! Every shape also takes optional `scale` (one or three factors), `rotate`
+//! (an axis and an angle in degrees) and `translate` fields, applied in that
+//! order. A shape given a name is not placed in the scene itself; `instance`
+//! blocks place copies of it that share its geometry:
+//!
+//! ```text
+//! mesh teapot { file "teapot.obj"; material ground }
+//! instance { object teapot; rotate 0 1 0 45; translate 2 0 0 }
+//! instance { object teapot; scale 0.5; translate -2 0 0 }
+//! ```
+//!
+//! Shapes made of a `diffuse_light` material are also sampled directly as
+//! lights.
+//!
+//! Named shapes are also the boundaries of volumes:
+//!
+//! ```text
+//! sphere fog_bounds { center 0 0 0; radius 5000; material glass }
+//! constant_medium { boundary fog_bounds; density 0.001; albedo 1 1 1 }
+//! ```
+
+use crate::box_shape::BoxShape;
+use crate::bvh::{BvhOptions, LinearBvh};
+use crate::camera::{self, CameraSettings};
+use crate::circle::Sphere;
+use crate::constant_medium::ConstantMedium;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::image_reader::{self, DecodeError};
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::moving_sphere::MovingSphere;
+use crate::obj::{self, ObjError};
+use crate::rect::{XyRect, XzRect, YzRect};
+use crate::scene::{Background, Scene};
+use crate::texture::{
+    Checker, Image, ImageOptions, Marble, Noise, SolidColor, Texture, TextureFilter, Turbulence,
+    WrapMode,
+};
+use crate::transform::{self, Transform};
+use crate::triangle::Triangle;
+use crate::vec::{self, Color};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A line and column in the scene source, both starting at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, std::io::Error),
+    Syntax(Position, String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            SceneError::Syntax(pos, message) => write!(f, "{}: {}", pos, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+fn error<T>(pos: Position, message: impl Into<String>) -> Result<T, SceneError> {
+    Err(SceneError::Syntax(pos, message.into()))
+}
+
+/// Reads and builds the scene at `path`. Relative file names inside the
+/// scene (image textures) are resolved against the scene's directory.
+/// `seed` drives the random choices made building its BVHs.
+pub fn load(path: &Path, seed: u64) -> Result<Scene, SceneError> {
+    let source =
+        std::fs::read_to_string(path).map_err(|err| SceneError::Io(path.to_owned(), err))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse(&source, base_dir, seed)
+}
+
+pub fn parse(source: &str, base_dir: &Path, seed: u64) -> Result<Scene, SceneError> {
+    let tokens = lex(source)?;
+    let blocks = Parser::new(tokens).blocks()?;
+    Builder::new(base_dir, seed).build(&blocks)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    LBrace,
+    RBrace,
+    EndOfField,
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Ident(s) => write!(f, "`{}`", s),
+            TokenKind::Number(n) => write!(f, "`{}`", n),
+            TokenKind::Str(s) => write!(f, "\"{}\"", s),
+            TokenKind::LBrace => write!(f, "`{{`"),
+            TokenKind::RBrace => write!(f, "`}}`"),
+            TokenKind::EndOfField => write!(f, "end of line"),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    pos: Position,
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, SceneError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut pos = Position { line: 1, column: 1 };
+
+    let advance = |c: char, pos: &mut Position| {
+        if c == '\n' {
+            pos.line += 1;
+            pos.column = 1;
+        } else {
+            pos.column += 1;
+        }
+    };
+
+    while let Some(&c) = chars.peek() {
+        let start = pos;
+        match c {
+            '#' => {
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                    advance(c, &mut pos);
+                }
+            }
+            '\n' | ';' => {
+                chars.next();
+                advance(c, &mut pos);
+                tokens.push(Token {
+                    kind: TokenKind::EndOfField,
+                    pos: start,
+                });
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                advance(c, &mut pos);
+            }
+            '{' | '}' => {
+                chars.next();
+                advance(c, &mut pos);
+                tokens.push(Token {
+                    kind: if c == '{' {
+                        TokenKind::LBrace
+                    } else {
+                        TokenKind::RBrace
+                    },
+                    pos: start,
+                });
+            }
+            '"' => {
+                chars.next();
+                advance(c, &mut pos);
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => {
+                            advance('"', &mut pos);
+                            break;
+                        }
+                        Some('\n') | None => return error(start, "unterminated string"),
+                        Some(c) => {
+                            advance(c, &mut pos);
+                            s.push(c);
+                        }
+                    }
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Str(s),
+                    pos: start,
+                });
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut s = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '.') {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                    advance(c, &mut pos);
+                }
+                match s.parse() {
+                    Ok(n) => tokens.push(Token {
+                        kind: TokenKind::Number(n),
+                        pos: start,
+                    }),
+                    Err(_) => return error(start, format!("invalid number `{}`", s)),
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut s = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                    advance(c, &mut pos);
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Ident(s),
+                    pos: start,
+                });
+            }
+            c => return error(start, format!("unexpected character `{}`", c)),
+        }
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        pos,
+    });
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Value {
+    Ident(String),
+    Number(f64),
+    Str(String),
+}
+
+#[derive(Debug)]
+struct Field {
+    key: String,
+    pos: Position,
+    values: Vec<(Value, Position)>,
+}
+
+/// `keyword [name [type]] { fields }`
+#[derive(Debug)]
+struct Block {
+    keyword: String,
+    pos: Position,
+    header: Vec<(String, Position)>,
+    fields: Vec<Field>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, next: 0 }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next]
+    }
+
+    fn bump(&mut self) -> Token {
+        let token = self.tokens[self.next].clone();
+        if token.kind != TokenKind::Eof {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn skip_field_ends(&mut self) {
+        while self.peek().kind == TokenKind::EndOfField {
+            self.bump();
+        }
+    }
+
+    fn blocks(&mut self) -> Result<Vec<Block>, SceneError> {
+        let mut blocks = Vec::new();
+        loop {
+            self.skip_field_ends();
+            let token = self.bump();
+            match token.kind {
+                TokenKind::Eof => return Ok(blocks),
+                TokenKind::Ident(keyword) => blocks.push(self.block(keyword, token.pos)?),
+                kind => return error(token.pos, format!("expected a block, found {}", kind)),
+            }
+        }
+    }
+
+    fn block(&mut self, keyword: String, pos: Position) -> Result<Block, SceneError> {
+        let mut header = Vec::new();
+        loop {
+            let token = self.bump();
+            match token.kind {
+                TokenKind::Ident(s) => header.push((s, token.pos)),
+                TokenKind::LBrace => break,
+                TokenKind::EndOfField => {}
+                kind => return error(token.pos, format!("expected `{{`, found {}", kind)),
+            }
+        }
+
+        let mut fields = Vec::new();
+        loop {
+            self.skip_field_ends();
+            let token = self.bump();
+            let key = match token.kind {
+                TokenKind::RBrace => break,
+                TokenKind::Ident(key) => key,
+                TokenKind::Eof => return error(pos, format!("unclosed `{}` block", keyword)),
+                kind => return error(token.pos, format!("expected a field name, found {}", kind)),
+            };
+
+            let mut values = Vec::new();
+            loop {
+                match &self.peek().kind {
+                    TokenKind::EndOfField | TokenKind::RBrace => break,
+                    TokenKind::Eof => return error(pos, format!("unclosed `{}` block", keyword)),
+                    TokenKind::LBrace => {
+                        return error(self.peek().pos, "unexpected `{` in field value")
+                    }
+                    _ => {}
+                }
+                let token = self.bump();
+                let value = match token.kind {
+                    TokenKind::Ident(s) => Value::Ident(s),
+                    TokenKind::Number(n) => Value::Number(n),
+                    TokenKind::Str(s) => Value::Str(s),
+                    _ => unreachable!(),
+                };
+                values.push((value, token.pos));
+            }
+
+            fields.push(Field {
+                key,
+                pos: token.pos,
+                values,
+            });
+        }
+
+        Ok(Block {
+            keyword,
+            pos,
+            header,
+            fields,
+        })
+    }
+}
+
+impl Field {
+    fn arity(&self, n: usize) -> Result<(), SceneError> {
+        if self.values.len() != n {
+            error(
+                self.pos,
+                format!(
+                    "`{}` takes {} value{}, found {}",
+                    self.key,
+                    n,
+                    if n == 1 { "" } else { "s" },
+                    self.values.len()
+                ),
+            )
+        } else {
+            Ok(())
+        }
+    }
+
+    fn number_at(&self, i: usize) -> Result<f64, SceneError> {
+        match &self.values[i] {
+            (Value::Number(n), _) => Ok(*n),
+            (_, pos) => error(*pos, format!("`{}` expects a number", self.key)),
+        }
+    }
+
+    fn number(&self) -> Result<f64, SceneError> {
+        self.arity(1)?;
+        self.number_at(0)
+    }
+
+    fn integer(&self) -> Result<u64, SceneError> {
+        let n = self.number()?;
+        if n < 0.0 || n.fract() != 0.0 || n > u32::MAX as f64 {
+            return error(
+                self.values[0].1,
+                format!("`{}` expects a whole number", self.key),
+            );
+        }
+        Ok(n as u64)
+    }
+
+    fn vec3(&self) -> Result<vec::Vec, SceneError> {
+        self.arity(3)?;
+        Ok(vec::Vec::new(
+            self.number_at(0)?,
+            self.number_at(1)?,
+            self.number_at(2)?,
+        ))
+    }
+
+    fn range(&self) -> Result<Range<f64>, SceneError> {
+        self.arity(2)?;
+        Ok(self.number_at(0)?..self.number_at(1)?)
+    }
+
+    fn ident(&self) -> Result<(&str, Position), SceneError> {
+        self.arity(1)?;
+        match &self.values[0] {
+            (Value::Ident(s), pos) => Ok((s, *pos)),
+            (_, pos) => error(*pos, format!("`{}` expects a name", self.key)),
+        }
+    }
+
+    fn string(&self) -> Result<&str, SceneError> {
+        self.arity(1)?;
+        match &self.values[0] {
+            (Value::Str(s), _) => Ok(s),
+            (_, pos) => error(*pos, format!("`{}` expects a quoted string", self.key)),
+        }
+    }
+}
+
+/// Field lookup for a single block which remembers what was asked for, so
+/// that leftover (misspelled or unsupported) fields can be reported.
+struct Fields<'a> {
+    block: &'a Block,
+    used: Vec<bool>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(block: &'a Block) -> Result<Self, SceneError> {
+        for (i, field) in block.fields.iter().enumerate() {
+            if let Some(dup) = block.fields[..i].iter().find(|f| f.key == field.key) {
+                return error(
+                    field.pos,
+                    format!("`{}` is already set on {}", field.key, dup.pos),
+                );
+            }
+        }
+        Ok(Self {
+            block,
+            used: vec![false; block.fields.len()],
+        })
+    }
+
+    fn get(&mut self, key: &str) -> Option<&'a Field> {
+        let i = self.block.fields.iter().position(|f| f.key == key)?;
+        self.used[i] = true;
+        Some(&self.block.fields[i])
+    }
+
+    fn require(&mut self, key: &str) -> Result<&'a Field, SceneError> {
+        match self.get(key) {
+            Some(field) => Ok(field),
+            None => error(
+                self.block.pos,
+                format!("`{}` is missing `{}`", self.block.keyword, key),
+            ),
+        }
+    }
+
+    fn finish(self) -> Result<(), SceneError> {
+        match self.used.iter().position(|used| !used) {
+            Some(i) => {
+                let field = &self.block.fields[i];
+                error(
+                    field.pos,
+                    format!("unknown field `{}` in `{}`", field.key, self.block.keyword),
+                )
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+struct Builder<'a> {
+    base_dir: &'a Path,
+    bvh_options: BvhOptions,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    objects: HashMap<String, Arc<dyn Hittable>>,
+    /// Named objects made of an emissive material.
+    emissive_objects: HashSet<String>,
+}
+
+impl<'a> Builder<'a> {
+    fn new(base_dir: &'a Path, seed: u64) -> Self {
+        Self {
+            base_dir,
+            bvh_options: BvhOptions {
+                seed,
+                ..Default::default()
+            },
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            objects: HashMap::new(),
+            emissive_objects: HashSet::new(),
+        }
+    }
+
+    fn build(mut self, blocks: &[Block]) -> Result<Scene, SceneError> {
+        let mut world = HittableList::new();
+        let mut lights = HittableList::new();
+        let mut camera = None;
+        let mut background = None;
+
+        for block in blocks {
+            match block.keyword.as_str() {
+                "camera" => {
+                    if camera.is_some() {
+                        return error(block.pos, "the scene already has a camera");
+                    }
+                    Self::header(block, &[])?;
+                    camera = Some(Self::camera(block)?);
+                }
+                "texture" => {
+                    let header = Self::header(block, &["name", "type"])?;
+                    let texture = self.texture(block, &header[1])?;
+                    self.textures.insert(header[0].0.clone(), texture);
+                }
+                "material" => {
+                    let header = Self::header(block, &["name", "type"])?;
+                    let material = self.material(block, &header[1])?;
+                    self.materials.insert(header[0].0.clone(), material);
+                }
+                "background" => {
+                    if background.is_some() {
+                        return error(block.pos, "the scene already has a background");
+                    }
+                    let header = Self::header(block, &["type"])?;
+                    background = Some(Self::background(block, &header[0])?);
+                }
+                "instance" => {
+                    Self::header(block, &[])?;
+                    let mut fields = Fields::new(block)?;
+                    let field = fields.require("object")?;
+                    let emissive = self.emissive_objects.contains(field.ident()?.0);
+                    let object = Self::transform(self.object_ref(field)?, &mut fields)?;
+                    fields.finish()?;
+                    if emissive {
+                        lights.add(object.clone());
+                    }
+                    world.add(object);
+                }
+                keyword => {
+                    let mut fields = Fields::new(block)?;
+                    let object = match self.shape(block, &mut fields)? {
+                        Some(object) => object,
+                        None => return error(block.pos, format!("unknown block `{}`", keyword)),
+                    };
+                    let object = Self::transform(object, &mut fields)?;
+                    let emissive = fields
+                        .get("material")
+                        .and_then(|field| self.material_ref(field).ok())
+                        .is_some_and(|material| material.is_emissive());
+                    fields.finish()?;
+
+                    // Named shapes are only placed through `instance` blocks.
+                    if block.header.is_empty() {
+                        if emissive {
+                            lights.add(object.clone());
+                        }
+                        world.add(object);
+                    } else {
+                        let header = Self::header(block, &["name"])?;
+                        if emissive {
+                            self.emissive_objects.insert(header[0].0.clone());
+                        }
+                        self.objects.insert(header[0].0.clone(), object);
+                    }
+                }
+            }
+        }
+
+        match camera {
+            Some(camera) => {
+                let mut scene = Scene::with_options(world, camera, &self.bvh_options);
+                scene.lights = lights;
+                if let Some(background) = background {
+                    scene.background = background;
+                }
+                Ok(scene)
+            }
+            None => error(
+                Position { line: 1, column: 1 },
+                "the scene has no `camera` block",
+            ),
+        }
+    }
+
+    /// Builds the shape described by a `sphere`, `box`, `mesh`, ... block, or
+    /// returns `None` if `block` is not a shape.
+    fn shape(
+        &self,
+        block: &Block,
+        fields: &mut Fields,
+    ) -> Result<Option<Arc<dyn Hittable>>, SceneError> {
+        let shape: Arc<dyn Hittable> = match block.keyword.as_str() {
+            "sphere" => Arc::new(Sphere::new(
+                fields.require("center")?.vec3()?,
+                fields.require("radius")?.number()?,
+                self.material_ref(fields.require("material")?)?,
+            )),
+            "moving_sphere" => Arc::new(MovingSphere::new(
+                fields.require("center0")?.vec3()?,
+                fields.require("center1")?.vec3()?,
+                fields.require("time")?.range()?,
+                fields.require("radius")?.number()?,
+                self.material_ref(fields.require("material")?)?,
+            )),
+            "xy_rect" | "xz_rect" | "yz_rect" => {
+                let axes = &block.keyword[..2];
+                let a = fields.require(&axes[..1])?.range()?;
+                let b = fields.require(&axes[1..])?.range()?;
+                let k = fields.require("k")?.number()?;
+                let material = self.material_ref(fields.require("material")?)?;
+                match axes {
+                    "xy" => Arc::new(XyRect::new(a, b, k, material)),
+                    "xz" => Arc::new(XzRect::new(a, b, k, material)),
+                    _ => Arc::new(YzRect::new(a, b, k, material)),
+                }
+            }
+            "box" => Arc::new(BoxShape::new(
+                fields.require("min")?.vec3()?,
+                fields.require("max")?.vec3()?,
+                self.material_ref(fields.require("material")?)?,
+            )),
+            "triangle" => Arc::new(Triangle::new(
+                fields.require("a")?.vec3()?,
+                fields.require("b")?.vec3()?,
+                fields.require("c")?.vec3()?,
+                self.material_ref(fields.require("material")?)?,
+            )),
+            "mesh" => {
+                let file = fields.require("file")?;
+                let path = self.base_dir.join(file.string()?);
+                let material = self.material_ref(fields.require("material")?)?;
+
+                let meshes = obj::load(&path, material).or_else(|err| match err {
+                    ObjError::Io(err) => Err(SceneError::Io(path.clone(), err)),
+                    err => error(file.pos, format!("{}: {}", path.display(), err)),
+                })?;
+                let mut objects = HittableList::new();
+                for mesh in meshes {
+                    let bvh = LinearBvh::with_options(
+                        mesh.triangles(),
+                        camera::SHUTTER,
+                        &self.bvh_options,
+                    )
+                    .or_else(|err| error(file.pos, format!("{}: {}", path.display(), err)))?;
+                    objects.add(Arc::new(bvh));
+                }
+                Arc::new(objects)
+            }
+            "constant_medium" => {
+                let field = fields.require("density")?;
+                let density = field.number()?;
+                let medium = ConstantMedium::from_texture(
+                    self.object_ref(fields.require("boundary")?)?,
+                    density,
+                    self.texture_ref(fields.require("albedo")?)?,
+                )
+                .or_else(|_| error(field.pos, "`density` must be a positive number"))?;
+                Arc::new(medium)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(shape))
+    }
+
+    /// Wraps `object` in a [`Transform`] if the block has `scale`, `rotate`
+    /// or `translate` fields. They always apply in that order, whatever
+    /// order they are written in.
+    fn transform(
+        object: Arc<dyn Hittable>,
+        fields: &mut Fields,
+    ) -> Result<Arc<dyn Hittable>, SceneError> {
+        let mut matrix = transform::Matrix::identity();
+        // Where the first transform field is, `None` if there is none.
+        let mut pos = None;
+
+        if let Some(field) = fields.get("scale") {
+            let factors = match field.values.len() {
+                1 => vec::Vec::from_element(field.number()?),
+                _ => field.vec3()?,
+            };
+            if factors.iter().any(|&f| f == 0.0) {
+                return error(field.pos, "`scale` factors must not be zero");
+            }
+            matrix = transform::scaling(factors) * matrix;
+            pos = pos.or(Some(field.pos));
+        }
+        if let Some(field) = fields.get("rotate") {
+            field.arity(4)?;
+            let axis = vec::Vec::new(
+                field.number_at(0)?,
+                field.number_at(1)?,
+                field.number_at(2)?,
+            );
+            if axis == vec::Vec::zeros() {
+                return error(field.pos, "`rotate` needs a non-zero axis");
+            }
+            matrix = transform::rotation(axis, field.number_at(3)?) * matrix;
+            pos = pos.or(Some(field.pos));
+        }
+        if let Some(field) = fields.get("translate") {
+            matrix = transform::translation(field.vec3()?) * matrix;
+            pos = pos.or(Some(field.pos));
+        }
+
+        let pos = match pos {
+            Some(pos) => pos,
+            None => return Ok(object),
+        };
+        // Scales that aren't zero can still be too small to invert.
+        match Transform::new(object, matrix) {
+            Some(transformed) => Ok(Arc::new(transformed)),
+            None => error(pos, "transform is not invertible"),
+        }
+    }
+
+    /// Checks that the block header has exactly the names listed in `names`.
+    fn header<'b>(
+        block: &'b Block,
+        names: &[&str],
+    ) -> Result<&'b [(String, Position)], SceneError> {
+        if block.header.len() > names.len() {
+            let (extra, pos) = &block.header[names.len()];
+            return error(*pos, format!("unexpected `{}` before `{{`", extra));
+        }
+        if block.header.len() < names.len() {
+            return error(
+                block.pos,
+                format!(
+                    "`{}` needs a {} before `{{`",
+                    block.keyword,
+                    names[block.header.len()]
+                ),
+            );
+        }
+        Ok(&block.header)
+    }
+
+    fn camera(block: &Block) -> Result<CameraSettings, SceneError> {
+        let mut fields = Fields::new(block)?;
+        let lookfrom = fields.require("lookfrom")?.vec3()?;
+        let lookat = fields.require("lookat")?.vec3()?;
+        let vup = match fields.get("vup") {
+            Some(field) => field.vec3()?,
+            None => vec::Vec::new(0.0, 1.0, 0.0),
+        };
+        let vfov = match fields.get("vfov") {
+            Some(field) => field.number()?,
+            None => 40.0,
+        };
+        let aperture = match fields.get("aperture") {
+            Some(field) => field.number()?,
+            None => 0.0,
+        };
+        let focus_dist = match fields.get("focus_dist") {
+            Some(field) => field.number()?,
+            None => (lookfrom - lookat).norm(),
+        };
+        fields.finish()?;
+
+        Ok(CameraSettings {
+            lookfrom,
+            lookat,
+            vup,
+            vfov,
+            aperture,
+            focus_dist,
+        })
+    }
+
+    fn background(
+        block: &Block,
+        (kind, pos): &(String, Position),
+    ) -> Result<Background, SceneError> {
+        let mut fields = Fields::new(block)?;
+        let background = match kind.as_str() {
+            "solid" => Background::Solid(fields.require("color")?.vec3()?),
+            "gradient" => Background::Gradient {
+                bottom: fields.require("bottom")?.vec3()?,
+                top: fields.require("top")?.vec3()?,
+            },
+            "none" => Background::None,
+            kind => return error(*pos, format!("unknown background type `{}`", kind)),
+        };
+        fields.finish()?;
+        Ok(background)
+    }
+
+    fn texture(
+        &self,
+        block: &Block,
+        (kind, pos): &(String, Position),
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        let mut fields = Fields::new(block)?;
+        let texture: Arc<dyn Texture> = match kind.as_str() {
+            "solid" => Arc::new(SolidColor::from_color(fields.require("color")?.vec3()?)),
+            "checker" => Arc::new(Checker::new(
+                self.texture_ref(fields.require("odd")?)?,
+                self.texture_ref(fields.require("even")?)?,
+            )),
+            "image" => {
+                let mut options = ImageOptions::default();
+                if let Some(field) = fields.get("filter") {
+                    options.filter = match field.ident()? {
+                        ("nearest", _) => TextureFilter::Nearest,
+                        ("bilinear", _) => TextureFilter::Bilinear,
+                        ("trilinear", _) => TextureFilter::Trilinear,
+                        (filter, pos) => {
+                            return error(pos, format!("unknown texture filter `{}`", filter))
+                        }
+                    };
+                }
+                if let Some(field) = fields.get("wrap") {
+                    options.wrap = match field.ident()? {
+                        ("clamp", _) => WrapMode::Clamp,
+                        ("repeat", _) => WrapMode::Repeat,
+                        ("mirror", _) => WrapMode::Mirror,
+                        (wrap, pos) => return error(pos, format!("unknown wrap mode `{}`", wrap)),
+                    };
+                }
+
+                let field = fields.require("file")?;
+                let path = self.base_dir.join(field.string()?);
+                let buffer = image_reader::load(&path).or_else(|err| match err {
+                    DecodeError::Io(err) => Err(SceneError::Io(path.clone(), err)),
+                    err => error(field.pos, format!("{}: {}", path.display(), err)),
+                })?;
+                Arc::new(Image::with_options(buffer, options))
+            }
+            "noise" | "turbulence" | "marble" => {
+                let seed = match fields.get("seed") {
+                    Some(field) => field.integer()?,
+                    None => 0,
+                };
+                let scale = match fields.get("scale") {
+                    Some(field) => field.number()?,
+                    None => 1.0,
+                };
+                let octaves = match fields.get("octaves") {
+                    Some(field) if kind != "noise" => match field.integer()? {
+                        octaves @ 1..=32 => octaves as u32,
+                        _ => return error(field.pos, "`octaves` must be between 1 and 32"),
+                    },
+                    _ => 7,
+                };
+                match kind.as_str() {
+                    "noise" => Arc::new(Noise::new(seed, scale)),
+                    "turbulence" => Arc::new(Turbulence::new(seed, scale, octaves)),
+                    _ => Arc::new(Marble::new(seed, scale, octaves)),
+                }
+            }
+            kind => return error(*pos, format!("unknown texture type `{}`", kind)),
+        };
+        fields.finish()?;
+        Ok(texture)
+    }
+
+    fn material(
+        &self,
+        block: &Block,
+        (kind, pos): &(String, Position),
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        let mut fields = Fields::new(block)?;
+        let material: Arc<dyn Material> = match kind.as_str() {
+            "lambertian" => Arc::new(Lambertian::from_texture(
+                self.texture_ref(fields.require("albedo")?)?,
+            )),
+            "metal" => Arc::new(Metal::from_texture(
+                self.texture_ref(fields.require("albedo")?)?,
+                match fields.get("fuzz") {
+                    Some(field) => field.number()?,
+                    None => 0.0,
+                },
+            )),
+            "dielectric" => Arc::new(Dielectric::new(fields.require("ir")?.number()?)),
+            "diffuse_light" => Arc::new(DiffuseLight::from_texture(
+                self.texture_ref(fields.require("emit")?)?,
+            )),
+            "isotropic" => Arc::new(Isotropic::from_texture(
+                self.texture_ref(fields.require("albedo")?)?,
+            )),
+            kind => return error(*pos, format!("unknown material type `{}`", kind)),
+        };
+        fields.finish()?;
+        Ok(material)
+    }
+
+    /// A color written inline or the name of a declared texture.
+    fn texture_ref(&self, field: &Field) -> Result<Arc<dyn Texture>, SceneError> {
+        let (name, pos) = match field.values.as_slice() {
+            [(Value::Ident(name), pos)] => (name, *pos),
+            [_, _, _] => {
+                let color: Color = field.vec3()?;
+                return Ok(Arc::new(SolidColor::from_color(color)));
+            }
+            _ => {
+                return error(
+                    field.pos,
+                    format!("`{}` expects a color or a texture name", field.key),
+                )
+            }
+        };
+        match self.textures.get(name) {
+            Some(texture) => Ok(texture.clone()),
+            None => error(pos, format!("no texture named `{}`", name)),
+        }
+    }
+
+    fn material_ref(&self, field: &Field) -> Result<Arc<dyn Material>, SceneError> {
+        let (name, pos) = field.ident()?;
+        match self.materials.get(name) {
+            Some(material) => Ok(material.clone()),
+            None => error(pos, format!("no material named `{}`", name)),
+        }
+    }
+
+    fn object_ref(&self, field: &Field) -> Result<Arc<dyn Hittable>, SceneError> {
+        let (name, pos) = field.ident()?;
+        match self.objects.get(name) {
+            Some(object) => Ok(object.clone()),
+            None => error(pos, format!("no object named `{}`", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The line, column and message of the error `source` fails with.
+    fn syntax_error(source: &str) -> (usize, usize, String) {
+        match parse(source, Path::new(""), 0) {
+            Ok(_) => panic!("expected an error"),
+            Err(SceneError::Syntax(pos, message)) => (pos.line, pos.column, message),
+            Err(err) => panic!("expected a syntax error, got {}", err),
+        }
+    }
+
+    #[test]
+    fn parses_example_scene() {
+        let source = "\
+camera { lookfrom 0 0 5; lookat 0 0 0 }
+texture checks checker { odd 0 0 0; even 1 1 1 }
+material ground lambertian { albedo checks }
+material mirror metal { albedo checks; fuzz 0.1 }
+sphere { center 0 0 0; radius 1; material mirror }
+box { min -1 -2 -1; max 1 -1 1; material ground; rotate 0 1 0 30 }
+";
+        assert!(parse(source, Path::new(""), 0).is_ok());
+    }
+
+    #[test]
+    fn reports_unknown_fields() {
+        let source = "material m lambertian {\n    albedo 1 1 1\n    shine 2\n}";
+        assert_eq!(
+            syntax_error(source),
+            (3, 5, "unknown field `shine` in `material`".to_owned())
+        );
+    }
+
+    #[test]
+    fn reports_unknown_blocks() {
+        assert_eq!(
+            syntax_error("\n  cylinder { radius 1 }"),
+            (2, 3, "unknown block `cylinder`".to_owned())
+        );
+    }
+
+    #[test]
+    fn reports_undeclared_names() {
+        let (line, column, message) =
+            syntax_error("sphere { center 0 0 0; radius 1; material nope }");
+        assert_eq!((line, column), (1, 43));
+        assert!(message.contains("nope"), "{}", message);
+    }
+
+    #[test]
+    fn reports_wrong_value_types() {
+        let source = "material m lambertian { albedo 1 1 1 }\nsphere { center 0 0 0; radius big; material m }";
+        assert_eq!(
+            syntax_error(source),
+            (2, 31, "`radius` expects a number".to_owned())
+        );
+    }
+
+    #[test]
+    fn reports_unclosed_blocks() {
+        let (line, column, message) = syntax_error("camera {\n  lookfrom 0 0 5\n");
+        assert_eq!((line, column), (1, 1));
+        assert_eq!(message, "unclosed `camera` block");
+    }
+
+    #[test]
+    fn rejects_transforms_that_cannot_be_inverted() {
+        let source = "material m lambertian { albedo 1 1 1 }\nsphere { center 0 0 0; radius 1; material m; scale 1e-200 }";
+        assert_eq!(
+            syntax_error(source),
+            (2, 46, "transform is not invertible".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_non_positive_densities() {
+        let source = "\
+material m lambertian { albedo 1 1 1 }
+sphere fog { center 0 0 0; radius 1; material m }
+constant_medium { boundary fog; density -1; albedo 1 1 1 }";
+        assert_eq!(
+            syntax_error(source),
+            (3, 33, "`density` must be a positive number".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_octaves() {
+        for octaves in ["0", "33", "4294967295"] {
+            let source = format!("texture t marble {{ octaves {} }}", octaves);
+            assert_eq!(
+                syntax_error(&source),
+                (1, 20, "`octaves` must be between 1 and 32".to_owned())
+            );
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::box_shape::BoxShape;
+use crate::bvh::BvhOptions;
+use crate::camera::CameraSettings;
+use crate::circle::Sphere;
+use crate::hittable_list::HittableList;
+use crate::image_reader::{self, DecodeError};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::moving_sphere::MovingSphere;
+use crate::rect::{XyRect, XzRect, YzRect};
+use crate::scene::{Background, Scene};
+use crate::texture::{self, Image, ImageOptions, Marble, SolidColor, Turbulence, WrapMode};
+use crate::transform::{self, Transform};
+use crate::util;
+use crate::vec::{self, Color, Point, Vec};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Names accepted by [`builtin`].
+pub const NAMES: &[&str] = &["random", "cornell", "perlin"];
+
+#[derive(Debug)]
+pub enum BuiltinError {
+    Unknown(String),
+    /// A texture the scene needs couldn't be read.
+    Texture(PathBuf, DecodeError),
+}
+
+impl fmt::Display for BuiltinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuiltinError::Unknown(name) => write!(f, "unknown scene `{}`", name),
+            BuiltinError::Texture(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for BuiltinError {}
+
+/// Builds the named scene, with `seed` driving every random choice made
+/// on the way, including those building its BVH.
+pub fn builtin(name: &str, seed: u64) -> Result<Scene, BuiltinError> {
+    match name {
+        "random" => random_scene(seed),
+        "cornell" => Ok(cornell_box(seed)),
+        "perlin" => Ok(perlin_spheres(seed)),
+        _ => Err(BuiltinError::Unknown(name.to_owned())),
+    }
+}
+
+/// Small spheres of random materials around three big ones. Reseeds the
+/// calling thread's generator, so a seed always gives the same layout. Two
+/// of the big spheres are textured with `res/2k_earth_daymap.png`, relative
+/// to the working directory.
+pub fn random_scene(seed: u64) -> Result<Scene, BuiltinError> {
+    util::seed(seed);
+    let mut world = HittableList::new();
+
+    let ground_material = Arc::new(Lambertian::from_texture(Arc::new(texture::Checker::new(
+        Arc::new(SolidColor::new(0.2, 0.3, 0.1)),
+        Arc::new(SolidColor::new(0.9, 0.9, 0.9)),
+    ))));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = util::random_f64();
+            let center = Vec::new(
+                a as f64 + 0.9 * util::random_f64(),
+                0.2,
+                b as f64 + 0.9 * util::random_f64(),
+            );
+
+            if (center - Point::new(4.0, 0.2, 0.0)).norm() > 0.9 {
+                let sphere_material: Arc<dyn Material> = {
+                    if choose_mat < 0.8 {
+                        // diffuse
+                        let albedo = vec::random().component_mul(&vec::random());
+                        Arc::new(Lambertian::new(albedo))
+                    } else if choose_mat < 0.95 {
+                        // metal
+                        let albedo = vec::random_range(0.5..1.0);
+                        let fuzz = util::random_f64_range(0.0..0.5);
+                        Arc::new(Metal::new(albedo, fuzz))
+                    } else {
+                        // glass
+                        Arc::new(Dielectric::new(1.5))
+                    }
+                };
+
+                if choose_mat < 0.8 {
+                    let center2 = center + Vec::new(0.0, util::random_f64_range(0.0..0.5), 0.0);
+                    world.add(Arc::new(MovingSphere::new(
+                        center,
+                        center2,
+                        0.0..1.0,
+                        0.2,
+                        sphere_material,
+                    )))
+                } else {
+                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)))
+                }
+            }
+        }
+    }
+
+    let material = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        material,
+    )));
+
+    let earth_path = Path::new("res/2k_earth_daymap.png");
+    let earth = image_reader::load(earth_path)
+        .map_err(|err| BuiltinError::Texture(earth_path.to_owned(), err))?;
+    let material_earth = Arc::new(Lambertian::from_texture(Arc::new(Image::with_options(
+        earth,
+        ImageOptions {
+            wrap: WrapMode::Repeat,
+            ..Default::default()
+        },
+    ))));
+    world.add(Arc::new(Sphere::new(
+        Point::new(-4.0, 1.0, 0.0),
+        1.0,
+        material_earth.clone(),
+    )));
+
+    world.add(Arc::new(Sphere::new(
+        Point::new(4.0, 1.0, 0.0),
+        1.0,
+        material_earth,
+    )));
+
+    let camera = CameraSettings {
+        lookfrom: Point::new(13.0, 2.0, 3.0),
+        lookat: Point::new(0.0, 0.0, 0.0),
+        vup: Vec::new(0.0, 1.0, 0.0),
+        vfov: 20.0,
+        aperture: 0.1,
+        focus_dist: 10.0,
+    };
+
+    Ok(scene(world, camera, seed))
+}
+
+/// The Cornell box, lit only by the square light in its ceiling. Meant to be
+/// rendered square.
+pub fn cornell_box(seed: u64) -> Scene {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+
+    world.add(Arc::new(YzRect::new(0.0..555.0, 0.0..555.0, 555.0, green)));
+    world.add(Arc::new(YzRect::new(0.0..555.0, 0.0..555.0, 0.0, red)));
+    let ceiling_light = Arc::new(XzRect::new(213.0..343.0, 227.0..332.0, 554.0, light));
+    world.add(ceiling_light.clone());
+    world.add(Arc::new(XzRect::new(
+        0.0..555.0,
+        0.0..555.0,
+        0.0,
+        white.clone(),
+    )));
+    world.add(Arc::new(XzRect::new(
+        0.0..555.0,
+        0.0..555.0,
+        555.0,
+        white.clone(),
+    )));
+    world.add(Arc::new(XyRect::new(
+        0.0..555.0,
+        0.0..555.0,
+        555.0,
+        white.clone(),
+    )));
+
+    // Both boxes are built at the origin and placed by rotating about y and
+    // then translating.
+    let place = |size: Vec, degrees: f64, offset: Vec| {
+        let shape = Arc::new(BoxShape::new(Point::zeros(), size, white.clone()));
+        let matrix = transform::translation(offset) * transform::rotation(Vec::y(), degrees);
+        Arc::new(Transform::new(shape, matrix).unwrap())
+    };
+    world.add(place(
+        Vec::new(165.0, 330.0, 165.0),
+        15.0,
+        Vec::new(265.0, 0.0, 295.0),
+    ));
+    world.add(place(
+        Vec::new(165.0, 165.0, 165.0),
+        -18.0,
+        Vec::new(130.0, 0.0, 65.0),
+    ));
+
+    let camera = CameraSettings {
+        lookfrom: Point::new(278.0, 278.0, -800.0),
+        lookat: Point::new(278.0, 278.0, 0.0),
+        vup: Vec::new(0.0, 1.0, 0.0),
+        vfov: 40.0,
+        aperture: 0.0,
+        focus_dist: 10.0,
+    };
+
+    let mut scene = scene(world, camera, seed);
+    scene.lights.add(ceiling_light);
+    scene.background = Background::None;
+    scene
+}
+
+/// A marble sphere on turbulent ground, showing off the noise textures.
+pub fn perlin_spheres(seed: u64) -> Scene {
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from_texture(Arc::new(Turbulence::new(
+        seed, 2.0, 7,
+    ))));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground,
+    )));
+    let marble = Arc::new(Lambertian::from_texture(Arc::new(Marble::new(
+        seed.wrapping_add(1),
+        4.0,
+        7,
+    ))));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 2.0, 0.0),
+        2.0,
+        marble,
+    )));
+
+    let camera = CameraSettings {
+        lookfrom: Point::new(13.0, 2.0, 3.0),
+        lookat: Point::new(0.0, 1.0, 0.0),
+        vup: Vec::new(0.0, 1.0, 0.0),
+        vfov: 20.0,
+        aperture: 0.0,
+        focus_dist: 10.0,
+    };
+
+    scene(world, camera, seed)
+}
+
+/// Puts `world` in a BVH built with `seed`.
+fn scene(world: HittableList, camera: CameraSettings, seed: u64) -> Scene {
+    let options = BvhOptions {
+        seed,
+        ..Default::default()
+    };
+    Scene::with_options(world, camera, &options)
+}
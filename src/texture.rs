@@ -1,10 +1,18 @@
-use std::fs::File;
-use std::rc::Rc;
+use std::path::Path;
+use std::sync::Arc;
 
+use crate::image_reader::{self, DecodeError, ImageBuffer};
+use crate::perlin::Perlin;
 use crate::vec::{Color, Point};
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn value(&self, uv: (f64, f64), p: &Point) -> Color;
+
+    /// The texture averaged over about `footprint` uv units around `uv`.
+    /// Only image textures filter; the rest look up the single point.
+    fn filtered_value(&self, uv: (f64, f64), p: &Point, _footprint: f64) -> Color {
+        self.value(uv, p)
+    }
 }
 
 pub struct SolidColor {
@@ -30,8 +38,8 @@ impl Texture for SolidColor {
 }
 
 pub struct Checker {
-    odd: Rc<dyn Texture>,
-    even: Rc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    even: Arc<dyn Texture>,
 }
 
 impl Texture for Checker {
@@ -40,73 +48,275 @@ impl Texture for Checker {
         let texture = if sines < 0.0 { &self.odd } else { &self.even };
         texture.value(uv, p)
     }
+
+    fn filtered_value(&self, uv: (f64, f64), p: &Point, footprint: f64) -> Color {
+        let sines = (10.0 * p.x).sin() * (10.0 * p.y).sin() * (10.0 * p.z).sin();
+        let texture = if sines < 0.0 { &self.odd } else { &self.even };
+        texture.filtered_value(uv, p, footprint)
+    }
 }
 
 impl Checker {
-    pub fn new(odd: Rc<dyn Texture>, even: Rc<dyn Texture>) -> Self {
+    pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>) -> Self {
         Self { odd, even }
     }
 }
 
-pub struct Image {
-    data: Vec<u8>,
-    info: png::OutputInfo,
-    bytes_per_pixel: usize,
+/// Gray Perlin noise, `scale` being its frequency.
+pub struct Noise {
+    noise: Perlin,
+    scale: f64,
 }
 
-impl Image {
-    pub fn from_png_file(file: &File) -> Result<Self, png::DecodingError> {
-        let mut reader = png::Decoder::new(file).read_info()?;
+impl Noise {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+        }
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, _uv: (f64, f64), p: &Point) -> Color {
+        Color::from_element(0.5 * (1.0 + self.noise.noise(&(self.scale * p))))
+    }
+}
+
+/// Several octaves of noise summed, which looks like clouds or dirt.
+pub struct Turbulence {
+    noise: Perlin,
+    scale: f64,
+    octaves: u32,
+}
+
+impl Turbulence {
+    pub fn new(seed: u64, scale: f64, octaves: u32) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            octaves,
+        }
+    }
+}
 
-        let mut data = vec![0; reader.output_buffer_size()];
+impl Texture for Turbulence {
+    fn value(&self, _uv: (f64, f64), p: &Point) -> Color {
+        Color::from_element(
+            self.noise
+                .turbulence(&(self.scale * p), self.octaves)
+                .min(1.0),
+        )
+    }
+}
 
-        let info = reader.next_frame(&mut data)?;
-        eprintln!("{:?}", info);
+/// Stripes along z whose phase is shifted by turbulence, giving veins like
+/// marble.
+pub struct Marble {
+    noise: Perlin,
+    scale: f64,
+    octaves: u32,
+}
 
-        Ok(Self {
-            data,
-            bytes_per_pixel: Self::calc_pixel_bytes(&info),
-            info,
-        })
+impl Marble {
+    pub fn new(seed: u64, scale: f64, octaves: u32) -> Self {
+        Self {
+            noise: Perlin::new(seed),
+            scale,
+            octaves,
+        }
     }
+}
 
-    fn calc_pixel_bytes(info: &png::OutputInfo) -> usize {
-        use png::BitDepth::*;
+impl Texture for Marble {
+    fn value(&self, _uv: (f64, f64), p: &Point) -> Color {
+        let phase = self.scale * p.z + 10.0 * self.noise.turbulence(p, self.octaves);
+        Color::from_element(0.5 * (1.0 + phase.sin()))
+    }
+}
 
-        let bit_depth = match info.bit_depth {
-            One => 1,
-            Two => 2,
-            Four => 4,
-            Eight => 8,
-            Sixteen => 16,
+/// How [`Image`] reconstructs colors between texels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// The texel under the point, blocky up close and aliased far away.
+    Nearest,
+    /// Blends the four nearest texels.
+    Bilinear,
+    /// Bilinear lookups in the two mip levels closest to the footprint's
+    /// size, blended together.
+    Trilinear,
+}
+
+/// What [`Image`] shows outside the `0..1` uv square.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Repeats the edge texels.
+    Clamp,
+    /// Tiles the image.
+    Repeat,
+    /// Tiles the image, flipping every other copy so that edges match.
+    Mirror,
+}
+
+impl WrapMode {
+    /// Brings texel index `i` into `0..n`.
+    fn apply(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n {
+                    i
+                } else {
+                    2 * n - 1 - i
+                }
+            }
         };
-        return bit_depth / 8 * 3;
+        i as usize
     }
 }
 
-impl Texture for Image {
-    fn value(&self, (u, v): (f64, f64), _p: &Point) -> Color {
-        let u = u.clamp(0.0, 1.0);
-        let v = 1.0 - v.clamp(0.0, 1.0);
-
-        let mut i = ((u * self.info.width as f64) as usize).clamp(0, self.info.width as usize - 1);
-        let mut j = ((v * self.info.height as f64) as usize).clamp(0, self.info.height as usize - 1);
+#[derive(Clone, Copy, Debug)]
+pub struct ImageOptions {
+    pub filter: TextureFilter,
+    pub wrap: WrapMode,
+}
 
-        if i >= self.info.width as usize {
-            i = self.info.width as usize - 1;
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            filter: TextureFilter::Trilinear,
+            wrap: WrapMode::Clamp,
         }
+    }
+}
+
+/// A decoded image stretched over the uv square, `v` pointing up.
+pub struct Image {
+    /// The full image followed by successively halved copies down to a
+    /// single texel. Only the first level is kept unless filtering is
+    /// trilinear.
+    levels: Vec<ImageBuffer>,
+    options: ImageOptions,
+}
 
-        if j >= self.info.height as usize {
-            j = self.info.height as usize - 1;
+impl Image {
+    pub fn new(buffer: ImageBuffer) -> Self {
+        Self::with_options(buffer, ImageOptions::default())
+    }
+
+    pub fn with_options(buffer: ImageBuffer, options: ImageOptions) -> Self {
+        let mut levels = vec![buffer];
+        if options.filter == TextureFilter::Trilinear {
+            loop {
+                let last = &levels[levels.len() - 1];
+                if last.width() == 1 && last.height() == 1 {
+                    break;
+                }
+                levels.push(downsample(last));
+            }
         }
+        Self { levels, options }
+    }
 
-        let index = j * self.info.line_size + i * self.bytes_per_pixel;
-        let color_scale = 1.0 / 255.0;
+    /// Loads any format [`image_reader::load`] knows about.
+    pub fn load(path: &Path) -> Result<Self, DecodeError> {
+        Ok(Self::new(image_reader::load(path)?))
+    }
+
+    pub fn buffer(&self) -> &ImageBuffer {
+        &self.levels[0]
+    }
 
-        Color::new(
-            color_scale * self.data[index] as f64,
-            color_scale * self.data[index + 1] as f64,
-            color_scale * self.data[index + 2] as f64,
+    fn texel(&self, level: &ImageBuffer, x: i64, y: i64) -> [f32; 4] {
+        level.pixel(
+            self.options.wrap.apply(x, level.width()),
+            self.options.wrap.apply(y, level.height()),
         )
     }
+
+    fn nearest(&self, (u, v): (f64, f64)) -> [f32; 4] {
+        let level = &self.levels[0];
+        let x = (u * level.width() as f64).floor() as i64;
+        let y = ((1.0 - v) * level.height() as f64).floor() as i64;
+        self.texel(level, x, y)
+    }
+
+    fn bilinear(&self, level: &ImageBuffer, (u, v): (f64, f64)) -> [f32; 4] {
+        // Texel centers sit at half-integer coordinates.
+        let x = u * level.width() as f64 - 0.5;
+        let y = (1.0 - v) * level.height() as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = ((x - x0) as f32, (y - y0) as f32);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = lerp(self.texel(level, x0, y0), self.texel(level, x0 + 1, y0), fx);
+        let bottom = lerp(
+            self.texel(level, x0, y0 + 1),
+            self.texel(level, x0 + 1, y0 + 1),
+            fx,
+        );
+        lerp(top, bottom, fy)
+    }
+
+    fn trilinear(&self, uv: (f64, f64), footprint: f64) -> [f32; 4] {
+        // The level whose texels are about as wide as the footprint.
+        let size = self.levels[0].width().max(self.levels[0].height());
+        let last = (self.levels.len() - 1) as f64;
+        let lod = (footprint * size as f64).log2().clamp(0.0, last);
+        if lod.is_nan() {
+            return self.bilinear(&self.levels[0], uv);
+        }
+
+        let fine = lod.floor() as usize;
+        let fraction = (lod - fine as f64) as f32;
+        let color = self.bilinear(&self.levels[fine], uv);
+        if fraction == 0.0 {
+            color
+        } else {
+            lerp(color, self.bilinear(&self.levels[fine + 1], uv), fraction)
+        }
+    }
+}
+
+fn lerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|c| a[c] + (b[c] - a[c]) * t)
+}
+
+/// Halves both sides with a 2x2 box filter. An odd last row or column
+/// gets averaged with itself, so no texel is skipped.
+fn downsample(image: &ImageBuffer) -> ImageBuffer {
+    let (width, height) = (image.width().div_ceil(2), image.height().div_ceil(2));
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (y0, y1) = (2 * y, (2 * y + 1).min(image.height() - 1));
+        for x in 0..width {
+            let (x0, x1) = (2 * x, (2 * x + 1).min(image.width() - 1));
+            let (a, b, c, d) = (
+                image.pixel(x0, y0),
+                image.pixel(x1, y0),
+                image.pixel(x0, y1),
+                image.pixel(x1, y1),
+            );
+            pixels.push([0, 1, 2, 3].map(|i| (a[i] + b[i] + c[i] + d[i]) / 4.0));
+        }
+    }
+    ImageBuffer::new(width, height, pixels)
+}
+
+impl Texture for Image {
+    fn value(&self, uv: (f64, f64), p: &Point) -> Color {
+        self.filtered_value(uv, p, 0.0)
+    }
+
+    fn filtered_value(&self, uv: (f64, f64), _p: &Point, footprint: f64) -> Color {
+        let [r, g, b, _] = match self.options.filter {
+            TextureFilter::Nearest => self.nearest(uv),
+            TextureFilter::Bilinear => self.bilinear(&self.levels[0], uv),
+            TextureFilter::Trilinear => self.trilinear(uv, footprint),
+        };
+        Color::new(r as f64, g as f64, b as f64)
+    }
 }
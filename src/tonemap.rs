@@ -0,0 +1,52 @@
+use crate::render::Framebuffer;
+use crate::vec::Color;
+
+/// Operators compressing linear radiance into the `0.0..=1.0` range that
+/// 8 and 16 bit formats can store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Cut everything above 1.0, which is what the renderer always did.
+    Clamp,
+    /// `x / (1 + x)` per channel.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+impl ToneMap {
+    pub const NAMES: &'static [&'static str] = &["clamp", "reinhard", "aces"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" => Some(ToneMap::Clamp),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "aces" => Some(ToneMap::Aces),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, color: Color) -> Color {
+        let f = |x: f64| {
+            let x = x.max(0.0);
+            match self {
+                ToneMap::Clamp => x.min(1.0),
+                ToneMap::Reinhard => x / (1.0 + x),
+                ToneMap::Aces => {
+                    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+                }
+            }
+        };
+        color.map(f)
+    }
+}
+
+/// Scales the framebuffer by `2^exposure` and applies `tone_map` to every
+/// pixel. The HDR writers expect the untouched framebuffer instead.
+pub fn tone_map(framebuffer: &Framebuffer, tone_map: ToneMap, exposure: f64) -> Framebuffer {
+    let scale = exposure.exp2();
+    let mut mapped = framebuffer.clone();
+    for pixel in mapped.pixels_mut() {
+        *pixel = tone_map.apply(*pixel * scale);
+    }
+    mapped
+}
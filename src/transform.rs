@@ -0,0 +1,150 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec::{self, Point};
+use nalgebra as na;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// An affine transformation in homogeneous coordinates.
+pub type Matrix = na::Matrix4<f64>;
+
+pub fn translation(offset: vec::Vec) -> Matrix {
+    Matrix::new_translation(&offset)
+}
+
+/// A rotation by `degrees` around `axis`, counter-clockwise when looking
+/// down the axis towards the origin.
+pub fn rotation(axis: vec::Vec, degrees: f64) -> Matrix {
+    na::Rotation3::from_axis_angle(&na::Unit::new_normalize(axis), degrees.to_radians())
+        .to_homogeneous()
+}
+
+pub fn scaling(factors: vec::Vec) -> Matrix {
+    Matrix::new_nonuniform_scaling(&factors)
+}
+
+/// Places an object in the world through an affine matrix. Rays are moved
+/// into the object's space instead of moving the object, so any number of
+/// instances can share the same geometry.
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    object_to_world: Matrix,
+    world_to_object: Matrix,
+    /// The inverse transpose of the linear part, which keeps normals
+    /// perpendicular to the surface under non-uniform scaling.
+    normal_matrix: na::Matrix3<f64>,
+}
+
+impl Transform {
+    /// Returns `None` if `matrix` is not invertible.
+    pub fn new(object: Arc<dyn Hittable>, matrix: Matrix) -> Option<Self> {
+        let inverse = matrix.try_inverse()?;
+        Some(Self {
+            object,
+            object_to_world: matrix,
+            world_to_object: inverse,
+            normal_matrix: inverse.fixed_view::<3, 3>(0, 0).transpose(),
+        })
+    }
+
+    pub fn translate(object: Arc<dyn Hittable>, offset: vec::Vec) -> Self {
+        Self::new(object, translation(offset)).unwrap()
+    }
+
+    pub fn rotate(object: Arc<dyn Hittable>, axis: vec::Vec, degrees: f64) -> Self {
+        Self::new(object, rotation(axis, degrees)).unwrap()
+    }
+
+    /// Panics if a factor is zero.
+    pub fn scale(object: Arc<dyn Hittable>, factors: vec::Vec) -> Self {
+        Self::new(object, scaling(factors)).expect("scale factors must not be zero")
+    }
+
+    fn to_world(&self, p: &Point) -> Point {
+        self.object_to_world.transform_point(&(*p).into()).coords
+    }
+
+    fn to_object(&self, p: &Point) -> Point {
+        self.world_to_object.transform_point(&(*p).into()).coords
+    }
+
+    /// `r` in the object's space. The direction is not renormalised, so `t`
+    /// means the same point along the ray in both spaces, and the cone keeps
+    /// its angle under uniform scaling.
+    fn ray_to_object(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.to_object(r.origin()),
+            self.world_to_object.transform_vector(r.direction()),
+            r.time(),
+        )
+        .with_spread(r.spread())
+    }
+
+    fn rec_to_world(&self, mut rec: HitRecord) -> HitRecord {
+        rec.p = self.to_world(&rec.p);
+        rec.normal = (self.normal_matrix * rec.normal).normalize();
+        rec
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, range: Range<f64>, sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        let rec = self.object.hit(&self.ray_to_object(r), range, sampler)?;
+        Some(self.rec_to_world(rec))
+    }
+
+    fn hit_counting(
+        &self,
+        r: &Ray,
+        range: Range<f64>,
+        tests: &mut usize,
+        sampler: &mut dyn Sampler,
+    ) -> Option<HitRecord> {
+        let rec = self
+            .object
+            .hit_counting(&self.ray_to_object(r), range, tests, sampler)?;
+        Some(self.rec_to_world(rec))
+    }
+
+    fn bounding_box(&self, time_range: Range<f64>) -> Option<Aabb> {
+        let bbox = self.object.bounding_box(time_range)?;
+        let (min, max) = (bbox.min(), bbox.max());
+
+        let mut minimum = Point::from_element(f64::INFINITY);
+        let mut maximum = Point::from_element(f64::NEG_INFINITY);
+        for corner in 0..8 {
+            let p = Point::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            let p = self.to_world(&p);
+            minimum = minimum.inf(&p);
+            maximum = maximum.sup(&p);
+        }
+        Some(Aabb::new(minimum, maximum))
+    }
+
+    fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<vec::Vec> {
+        let direction = self
+            .object
+            .sample_towards(&self.to_object(origin), sampler)?;
+        Some(self.object_to_world.transform_vector(&direction))
+    }
+
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        let moved = self.ray_to_object(r);
+        let pdf = self.object.pdf_towards(&moved, sampler);
+        if pdf == 0.0 {
+            return 0.0;
+        }
+
+        // The linear part `m` maps a unit direction `d` to `m d / |m d|`,
+        // scaling solid angles around it by `|det m| / |m d|^3`.
+        let m = self.object_to_world.fixed_view::<3, 3>(0, 0);
+        let stretch = (m * moved.direction().normalize()).norm();
+        pdf * stretch.powi(3) / m.determinant().abs()
+    }
+}
@@ -0,0 +1,207 @@
+use crate::aabb::Aabb;
+use crate::hittable::{self, HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec::{self, Point};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Vertex buffers shared by all the triangles of a mesh. Each triangle
+/// indexes three vertices; `normals` and `uvs`, when present, hold one entry
+/// per vertex.
+pub struct TriangleMesh {
+    positions: Vec<Point>,
+    normals: Option<Vec<vec::Vec>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+    material: Arc<dyn Material>,
+}
+
+impl TriangleMesh {
+    /// Panics if an index is out of range or if `normals` or `uvs` are not
+    /// as long as `positions`.
+    pub fn new(
+        positions: Vec<Point>,
+        normals: Option<Vec<vec::Vec>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        indices: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        assert!(indices.iter().flatten().all(|&i| i < positions.len()));
+        assert!(normals.as_ref().is_none_or(|n| n.len() == positions.len()));
+        assert!(uvs.as_ref().is_none_or(|uv| uv.len() == positions.len()));
+
+        Self {
+            positions,
+            normals: normals.map(|normals| normals.iter().map(|n| n.normalize()).collect()),
+            uvs,
+            indices,
+            material,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// One hittable per face, all pointing into this mesh's buffers.
+    pub fn triangles(self: &Arc<Self>) -> Vec<Arc<dyn Hittable>> {
+        (0..self.indices.len())
+            .map(|index| {
+                Arc::new(Triangle {
+                    mesh: self.clone(),
+                    index,
+                }) as Arc<dyn Hittable>
+            })
+            .collect()
+    }
+}
+
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    index: usize,
+}
+
+impl Triangle {
+    /// A mesh holding a single triangle with no normals or uvs.
+    pub fn new(a: Point, b: Point, c: Point, material: Arc<dyn Material>) -> Self {
+        Self {
+            mesh: Arc::new(TriangleMesh::new(
+                vec![a, b, c],
+                None,
+                None,
+                vec![[0, 1, 2]],
+                material,
+            )),
+            index: 0,
+        }
+    }
+
+    fn vertices(&self) -> [usize; 3] {
+        self.mesh.indices[self.index]
+    }
+}
+
+impl Hittable for Triangle {
+    /// Möller-Trumbore intersection.
+    fn hit(&self, r: &Ray, range: Range<f64>, _sampler: &mut dyn Sampler) -> Option<HitRecord> {
+        let [i0, i1, i2] = self.vertices();
+        let (p0, p1, p2) = (
+            self.mesh.positions[i0],
+            self.mesh.positions[i1],
+            self.mesh.positions[i2],
+        );
+
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+        let pvec = r.direction().cross(&edge2);
+        let det = edge1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.origin() - p0;
+        let u = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let qvec = tvec.cross(&edge1);
+        let v = r.direction().dot(&qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(&qvec) * inv_det;
+        if !range.contains(&t) {
+            return None;
+        }
+
+        let w = 1.0 - u - v;
+        let (uv0, uv1, uv2) = match &self.mesh.uvs {
+            Some(uvs) => (uvs[i0], uvs[i1], uvs[i2]),
+            None => ((0.0, 0.0), (1.0, 0.0), (0.0, 1.0)),
+        };
+        let uv = (
+            w * uv0.0 + u * uv1.0 + v * uv2.0,
+            w * uv0.1 + u * uv1.1 + v * uv2.1,
+        );
+
+        let normal = edge1.cross(&edge2);
+        let geometric_normal = normal.normalize();
+        let mut rec = HitRecord::new(t, r, geometric_normal, self.mesh.material.clone(), uv);
+
+        // Scale the cone by the square root of the uv area per unit of
+        // surface area, both doubled here.
+        let uv_area = ((uv1.0 - uv0.0) * (uv2.1 - uv0.1) - (uv2.0 - uv0.0) * (uv1.1 - uv0.1)).abs();
+        rec.footprint = r.footprint(t) * (uv_area / normal.norm()).sqrt();
+
+        // Shade with the interpolated normal, flipped onto the side the ray
+        // came from like the geometric one.
+        if let Some(normals) = &self.mesh.normals {
+            let shading_normal = (w * normals[i0] + u * normals[i1] + v * normals[i2]).normalize();
+            rec.normal = if shading_normal.dot(&rec.normal) < 0.0 {
+                -shading_normal
+            } else {
+                shading_normal
+            };
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time_range: Range<f64>) -> Option<Aabb> {
+        let [i0, i1, i2] = self.vertices();
+        let (p0, p1, p2) = (
+            self.mesh.positions[i0],
+            self.mesh.positions[i1],
+            self.mesh.positions[i2],
+        );
+
+        // Pad so that axis-aligned triangles don't get a flat box.
+        let padding = vec::Vec::from_element(1e-4);
+        Some(Aabb::new(
+            p0.inf(&p1).inf(&p2) - padding,
+            p0.sup(&p1).sup(&p2) + padding,
+        ))
+    }
+
+    fn sample_towards(&self, origin: &Point, sampler: &mut dyn Sampler) -> Option<vec::Vec> {
+        let [i0, i1, i2] = self.vertices();
+        let (p0, p1, p2) = (
+            self.mesh.positions[i0],
+            self.mesh.positions[i1],
+            self.mesh.positions[i2],
+        );
+
+        // Folding the unit square onto the triangle keeps the points uniform.
+        let u = sampler.get_2d();
+        let root = u.0.sqrt();
+        let (b0, b1) = (1.0 - root, u.1 * root);
+        Some(b0 * p0 + b1 * p1 + (1.0 - b0 - b1) * p2 - origin)
+    }
+
+    fn pdf_towards(&self, r: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        let [i0, i1, i2] = self.vertices();
+        let (p0, p1, p2) = (
+            self.mesh.positions[i0],
+            self.mesh.positions[i1],
+            self.mesh.positions[i2],
+        );
+
+        match self.hit(r, 0.001..f64::INFINITY, sampler) {
+            Some(rec) => {
+                let normal = (p1 - p0).cross(&(p2 - p0));
+                let area = 0.5 * normal.norm();
+                hittable::area_to_solid_angle_pdf(r, rec.t, &normal.normalize(), area)
+            }
+            None => 0.0,
+        }
+    }
+}
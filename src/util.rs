@@ -7,6 +7,12 @@ thread_local! {
     static DIST: Box<Uniform<f64>> = Box::new(Uniform::new(0.0, 1.0));
 }
 
+/// Reseeds the calling thread's generator. The renderer calls this before
+/// every pixel sample, with a seed from [`hash`].
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
 pub fn random_f64() -> f64 {
     RNG.with(|rng| DIST.with(|dist| dist.sample(&mut *rng.borrow_mut())))
 }
@@ -18,3 +24,21 @@ pub fn random_f64_range(range: Range<f64>) -> f64 {
 pub fn random_i32_range(range: RangeInclusive<i32>) -> i32 {
     RNG.with(|rng| rng.borrow_mut().gen_range(range))
 }
+
+/// Mixes `values` into `seed`. Related inputs, like the coordinates of
+/// neighbouring pixels, give unrelated results.
+pub fn hash(seed: u64, values: &[u64]) -> u64 {
+    values.iter().fold(
+        mix(seed),
+        |h, &v| mix(h ^ mix(v.wrapping_add(GOLDEN_GAMMA))),
+    )
+}
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// The SplitMix64 finalizer.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
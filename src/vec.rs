@@ -1,29 +1,12 @@
 use crate::util;
 use nalgebra as na;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
 use std::ops::Range;
 
 pub type Vec = na::Vector3<f64>;
 pub type Point = na::Vector3<f64>;
 pub type Color = na::Vector3<f64>;
 
-pub fn write_color(
-    writer: &mut dyn std::io::Write,
-    pixel_color: Color,
-    samples_per_pixel: u32,
-) -> std::io::Result<()> {
-    let scale = 1.0 / samples_per_pixel as f64;
-
-    let f = |x: f64| (256.0 * (x * scale).sqrt().clamp(0.0, 0.999)) as u32;
-
-    write!(
-        writer,
-        "{} {} {}\n",
-        f(pixel_color.x),
-        f(pixel_color.y),
-        f(pixel_color.z)
-    )
-}
-
 pub fn random() -> Vec {
     Vec::new(util::random_f64(), util::random_f64(), util::random_f64())
 }
@@ -36,30 +19,50 @@ pub fn random_range(range: Range<f64>) -> Vec {
     )
 }
 
-pub fn random_in_unit_sphere() -> Vec {
-    loop {
-        let p = random_range(-1.0..1.0);
-        if p.norm_squared() < 1.0 {
-            return p;
-        }
+/// Maps `u` from the unit square to the unit disk in the xy plane,
+/// uniformly and keeping nearby points together (Shirley and Chiu's
+/// concentric mapping).
+pub fn unit_disk_sample(u: (f64, f64)) -> Vec {
+    let (a, b) = (2.0 * u.0 - 1.0, 2.0 * u.1 - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return Vec::zeros();
     }
+    let (r, phi) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+    };
+    Vec::new(r * phi.cos(), r * phi.sin(), 0.0)
 }
 
-pub fn random_unit_vector() -> Vec {
-    random_in_unit_sphere().normalize()
+/// Maps `u` from the unit square to a uniformly distributed unit vector.
+pub fn unit_vector_sample(u: (f64, f64)) -> Vec {
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+    Vec::new(r * phi.cos(), r * phi.sin(), z)
 }
 
-pub fn random_in_unit_disk() -> Vec {
-    loop {
-        let p = Vec::new(
-            util::random_f64_range(-1.0..1.0),
-            util::random_f64_range(-1.0..1.0),
-            0.0,
-        );
-        if p.norm_squared() < 1.0 {
-            return p;
-        }
-    }
+/// Maps `u` and `w` from the unit cube to a uniformly distributed point in
+/// the unit sphere.
+pub fn unit_sphere_sample(u: (f64, f64), w: f64) -> Vec {
+    w.cbrt() * unit_vector_sample(u)
+}
+
+/// Maps `u` from the unit square to a unit vector around the unit vector
+/// `normal`, with a density of `cos θ / π`.
+pub fn cosine_sample(normal: &Vec, u: (f64, f64)) -> Vec {
+    let d = unit_disk_sample(u);
+    let z = (1.0 - d.norm_squared()).max(0.0).sqrt();
+    let (a, b) = orthonormal_basis(normal);
+    d.x * a + d.y * b + z * normal
+}
+
+/// Two unit vectors completing the unit vector `w` to an orthonormal basis.
+pub fn orthonormal_basis(w: &Vec) -> (Vec, Vec) {
+    let a = if w.x.abs() > 0.9 { Vec::y() } else { Vec::x() };
+    let v = w.cross(&a).normalize();
+    (w.cross(&v), v)
 }
 
 pub fn near_zero(vec: Vec) -> bool {
@@ -68,13 +71,13 @@ pub fn near_zero(vec: Vec) -> bool {
 }
 
 pub fn reflect(vec: &Vec, normal: &Vec) -> Vec {
-    vec - 2.0 * vec.dot(&normal) * normal
+    vec - 2.0 * vec.dot(normal) * normal
 }
 
 pub fn refract(uv: &Vec, normal: &Vec, etai_over_etat: f64) -> Vec {
     let cos_theta = normal.dot(&-uv).min(1.0);
     let r_out_perp = etai_over_etat * (uv + cos_theta * normal);
-    let r_out_parallel = -(((1.0 - r_out_perp.norm_squared()) as f64).abs().sqrt()) * normal;
+    let r_out_parallel = -((1.0 - r_out_perp.norm_squared()).abs().sqrt()) * normal;
 
     r_out_perp + r_out_parallel
 }